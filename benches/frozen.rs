@@ -0,0 +1,33 @@
+// Lookups in a map and in its frozen copy. Run with `cargo bench`.
+
+use avltree::AVLTreeMap;
+use rand::{rngs::StdRng, Rng as _, SeedableRng as _};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+fn time<F: FnMut() -> usize>(mut lookups: F) -> Duration {
+    let start = Instant::now();
+    black_box(lookups());
+    start.elapsed()
+}
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(0);
+    for len in [1 << 10, 1 << 16, 1 << 20] {
+        let map: AVLTreeMap<u64, u64> = (0..len).map(|_| (rng.gen(), rng.gen())).collect();
+        let frozen = map.freeze();
+        let probes: Vec<u64> = (0..1_000_000)
+            .map(|index| match index % 2 {
+                0 => *map.nth_key_value(rng.gen_range(0..map.len())).unwrap().0,
+                _ => rng.gen(),
+            })
+            .collect();
+        let tree = time(|| probes.iter().filter(|key| map.get(*key).is_some()).count());
+        let eytzinger = time(|| probes.iter().filter(|key| frozen.get(*key).is_some()).count());
+        println!(
+            "{len:>8} keys, {} lookups: tree {tree:?}, frozen {eytzinger:?} ({:.2}x)",
+            probes.len(),
+            tree.as_secs_f64() / eytzinger.as_secs_f64()
+        );
+    }
+}
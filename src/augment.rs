@@ -0,0 +1,43 @@
+#![forbid(unsafe_code)]
+
+// A monoid over the entries of a subtree. Every node caches the summary of its
+// subtree, so `combine` has to be associative and `identity` neutral for it:
+// range folds glue together partial subtrees in key order.
+pub trait Augment<K, V> {
+    type Summary: Clone;
+
+    fn identity() -> Self::Summary;
+
+    fn lift(key: &K, value: &V) -> Self::Summary;
+
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary;
+
+    fn summarize(
+        left: Option<&Self::Summary>,
+        key: &K,
+        value: &V,
+        right: Option<&Self::Summary>,
+    ) -> Self::Summary {
+        let node = Self::lift(key, value);
+        let node = match left {
+            Some(left) => Self::combine(left, &node),
+            None => node,
+        };
+        match right {
+            Some(right) => Self::combine(&node, right),
+            None => node,
+        }
+    }
+}
+
+impl<K, V> Augment<K, V> for () {
+    type Summary = ();
+
+    fn identity() {}
+
+    fn lift(_: &K, _: &V) {}
+
+    fn combine(_: &(), _: &()) {}
+
+    fn summarize(_: Option<&()>, _: &K, _: &V, _: Option<&()>) {}
+}
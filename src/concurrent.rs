@@ -0,0 +1,298 @@
+#![forbid(unsafe_code)]
+
+use crate::iter::Iter;
+use crate::tree::AVLTreeMap;
+use std::borrow::Borrow;
+use std::ops::{Bound, RangeBounds};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{RwLock, RwLockReadGuard};
+
+// The keys from `start` (from the lowest key for the first shard) up to the
+// start of the next shard.
+struct Shard<K: Ord, V> {
+    start: Option<K>,
+    map: RwLock<AVLTreeMap<K, V>>,
+}
+
+// A map for many threads at once. The key space is cut into shards, each an
+// `AVLTreeMap` behind its own lock, so writers to different key ranges do
+// not wait for each other. A shard that outgrows `max_shard_len` is split in
+// half with `split_off`, and one that shrinks below a quarter of it is merged
+// into a neighbor with `append`.
+//
+// Locks are always taken in the same order, the shard list first and then
+// shards from left to right, so operations spanning several shards see a
+// consistent state without deadlocks.
+pub struct ConcurrentAVLMap<K: Ord, V> {
+    shards: RwLock<Vec<Shard<K, V>>>,
+    len: AtomicUsize,
+    max_shard_len: usize,
+}
+
+impl<K: Ord + Clone, V: Clone> Default for ConcurrentAVLMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> ConcurrentAVLMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_max_shard_len(1 << 16)
+    }
+
+    pub fn with_max_shard_len(max_shard_len: usize) -> Self {
+        assert!(max_shard_len >= 4, "max shard len (is {max_shard_len}) should be at least 4");
+        Self {
+            shards: RwLock::new(vec![Shard {
+                start: None,
+                map: RwLock::new(AVLTreeMap::new()),
+            }]),
+            len: AtomicUsize::new(0),
+            max_shard_len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.read().unwrap().len()
+    }
+
+    fn shard_index<Q>(shards: &[Shard<K, V>], key: &Q) -> usize
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        shards.partition_point(|shard| shard.start.as_ref().is_none_or(|start| start.borrow() <= key)) - 1
+    }
+
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        let (old, split) = {
+            let shards = self.shards.read().unwrap();
+            let mut map = shards[Self::shard_index(&shards, &key)].map.write().unwrap();
+            let old = map.insert(key, value);
+            if old.is_none() {
+                self.len.fetch_add(1, Ordering::AcqRel);
+            }
+            (old, map.len() > self.max_shard_len)
+        };
+        if split {
+            self.rebalance();
+        }
+        old
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        let (old, merge) = {
+            let shards = self.shards.read().unwrap();
+            let index = Self::shard_index(&shards, key);
+            let (old, len) = {
+                let mut map = shards[index].map.write().unwrap();
+                (map.remove(key), map.len())
+            };
+            if old.is_some() {
+                self.len.fetch_sub(1, Ordering::AcqRel);
+            }
+            // Neighbors are locked one at a time, once this shard is
+            // unlocked, so the order above is kept.
+            let neighbors = [index.checked_sub(1), Some(index + 1)];
+            let merge = old.is_some()
+                && neighbors.into_iter().flatten().filter_map(|other| shards.get(other)).any(|other| {
+                    self.mergeable(len, other.map.read().unwrap().len())
+                });
+            (old, merge)
+        };
+        if merge {
+            self.rebalance();
+        }
+        old
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        let shards = self.shards.read().unwrap();
+        let map = shards[Self::shard_index(&shards, key)].map.read().unwrap();
+        map.get(key).cloned()
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        let shards = self.shards.read().unwrap();
+        let map = shards[Self::shard_index(&shards, key)].map.read().unwrap();
+        map.contains_key(key)
+    }
+
+    // Whether neighboring shards of these lengths are to be merged: when one
+    // is small and the result stays well below the limit, so the two halves
+    // of a split are not merged right back.
+    fn mergeable(&self, len: usize, other_len: usize) -> bool {
+        let quarter = self.max_shard_len / 4;
+        (len < quarter || other_len < quarter) && len + other_len <= self.max_shard_len / 2
+    }
+
+    // Splits the shards that grew too large and merges the ones that shrank
+    // too far, with every other operation locked out.
+    fn rebalance(&self) {
+        let mut shards = self.shards.write().unwrap();
+        let mut index = 0;
+        while index < shards.len() {
+            let len = shards[index].map.get_mut().unwrap().len();
+            if len > self.max_shard_len {
+                let map = shards[index].map.get_mut().unwrap();
+                let start = map.nth_key_value(len / 2).unwrap().0.clone();
+                let upper = map.split_off(&start);
+                shards.insert(
+                    index + 1,
+                    Shard {
+                        start: Some(start),
+                        map: RwLock::new(upper),
+                    },
+                );
+                continue;
+            }
+            let next_len = shards.get_mut(index + 1).map(|next| next.map.get_mut().unwrap().len());
+            match next_len {
+                Some(next_len) if self.mergeable(len, next_len) => {
+                    let mut upper = shards.remove(index + 1).map.into_inner().unwrap();
+                    shards[index].map.get_mut().unwrap().append(&mut upper);
+                }
+                _ => index += 1,
+            }
+        }
+    }
+
+    // Read guards on the shards from the first one on, for as long as `more`
+    // asks for the next one.
+    fn read_in_order<'a, F>(shards: &'a [Shard<K, V>], mut more: F) -> Vec<RwLockReadGuard<'a, AVLTreeMap<K, V>>>
+        where
+            F: FnMut(&Shard<K, V>) -> bool,
+    {
+        let mut guards = Vec::new();
+        for shard in shards {
+            if !more(shard) {
+                break;
+            }
+            guards.push(shard.map.read().unwrap());
+        }
+        guards
+    }
+
+    // The number of keys below `key`, counted across the shards at a single
+    // point in time.
+    pub fn rank<Q>(&self, key: &Q) -> usize
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        let shards = self.shards.read().unwrap();
+        let guards = Self::read_in_order(&shards, |shard| shard.start.as_ref().is_none_or(|start| start.borrow() <= key));
+        let (last, before) = guards.split_last().unwrap();
+        let below = last.rank_range((Bound::Unbounded, Bound::Excluded(key))).end;
+        before.iter().map(|map| map.len()).sum::<usize>() + below
+    }
+
+    // The entry of rank `index` across all shards, at a single point in time.
+    pub fn nth(&self, mut index: usize) -> Option<(K, V)> {
+        let shards = self.shards.read().unwrap();
+        let mut guards = Vec::new();
+        for shard in shards.iter() {
+            let map = shard.map.read().unwrap();
+            if index < map.len() {
+                let (key, value) = map.nth_key_value(index).unwrap();
+                return Some((key.clone(), value.clone()));
+            }
+            index -= map.len();
+            guards.push(map);
+        }
+        None
+    }
+
+    // A snapshot of the entries within `range`. The shards involved are all
+    // locked at once and cloned, which takes O(1) each, so the snapshot is
+    // one consistent state that later writes do not change.
+    pub fn range_snapshot<R: RangeBounds<K>>(&self, range: R) -> RangeSnapshot<K, V> {
+        let (start, end) = (range.start_bound().cloned(), range.end_bound().cloned());
+        let shards = self.shards.read().unwrap();
+        let first = match &start {
+            Bound::Included(key) | Bound::Excluded(key) => Self::shard_index(&shards, key),
+            Bound::Unbounded => 0,
+        };
+        let guards = Self::read_in_order(&shards[first..], |shard| match (&shard.start, &end) {
+            (Some(start), Bound::Included(end)) => start <= end,
+            (Some(start), Bound::Excluded(end)) => start < end,
+            _ => true,
+        });
+        RangeSnapshot {
+            shards: guards.iter().map(|map| (*map).clone()).collect(),
+            start,
+            end,
+        }
+    }
+
+    pub fn snapshot(&self) -> RangeSnapshot<K, V> {
+        self.range_snapshot(..)
+    }
+}
+
+// Entries of a `ConcurrentAVLMap` as they were at one point in time.
+pub struct RangeSnapshot<K: Ord, V> {
+    shards: Vec<AVLTreeMap<K, V>>,
+    start: Bound<K>,
+    end: Bound<K>,
+}
+
+impl<K: Ord, V> RangeSnapshot<K, V> {
+    pub fn iter(&self) -> RangeSnapshotIter<'_, K, V> {
+        RangeSnapshotIter {
+            snapshot: self,
+            shard: 0,
+            current: None,
+        }
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a RangeSnapshot<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = RangeSnapshotIter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct RangeSnapshotIter<'a, K: Ord, V> {
+    snapshot: &'a RangeSnapshot<K, V>,
+    shard: usize,
+    current: Option<Iter<'a, K, V>>,
+}
+
+impl<'a, K: Ord, V> Iterator for RangeSnapshotIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.current.as_mut().and_then(Iterator::next) {
+                return Some(entry);
+            }
+            let map = self.snapshot.shards.get(self.shard)?;
+            self.shard += 1;
+            self.current = Some(map.range((self.snapshot.start.as_ref(), self.snapshot.end.as_ref())));
+        }
+    }
+}
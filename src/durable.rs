@@ -0,0 +1,362 @@
+#![forbid(unsafe_code)]
+
+// A map that survives restarts. The directory holds a snapshot (see
+// `snapshot.rs`) and a log of the changes made since. Each log record is
+//
+//   body length: u32, CRC-32 of body: u32, body
+//
+// where the body is tag 1 with a key and a value for an insert, or tag 2 with
+// a key for a removal, each a length-prefixed `Codec` field.
+
+use crate::augment::Augment;
+use crate::snapshot::{crc32, put_field, take_field, Codec, SnapshotError};
+use crate::tree::AVLTreeMap;
+use std::borrow::Borrow;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const SNAPSHOT: &str = "snapshot";
+const SNAPSHOT_TMP: &str = "snapshot.tmp";
+const LOG: &str = "log";
+const INSERT: u8 = 1;
+const REMOVE: u8 = 2;
+
+// When the log is flushed to disk. Whatever policy, `sync` and `compact`
+// always flush.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncPolicy {
+    // After every change, which is then durable once the call returns.
+    Always,
+    // After every `n` changes. A crash loses at most the last `n - 1`.
+    Every(usize),
+    // Only on `sync` and `compact`; otherwise whenever the OS does.
+    Never,
+}
+
+#[derive(Debug)]
+pub enum DurableError {
+    Io(io::Error),
+    Snapshot(SnapshotError),
+    // A damaged log record with valid records after it, which is not what
+    // an interrupted append leaves behind.
+    CorruptLog { offset: u64 },
+}
+
+impl fmt::Display for DurableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "durable map I/O failed: {error}"),
+            Self::Snapshot(error) => error.fmt(f),
+            Self::CorruptLog { offset } => write!(f, "log record at byte {offset} is corrupt"),
+        }
+    }
+}
+
+impl std::error::Error for DurableError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Snapshot(error) => Some(error),
+            Self::CorruptLog { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for DurableError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<SnapshotError> for DurableError {
+    fn from(error: SnapshotError) -> Self {
+        Self::Snapshot(error)
+    }
+}
+
+// An `AVLTreeMap` whose changes are appended to a write-ahead log before they
+// are applied. Reads go straight to the map in memory.
+pub struct DurableAVLMap<K: Ord + Codec, V: Codec, A: Augment<K, V> = ()> {
+    map: AVLTreeMap<K, V, A>,
+    dir: PathBuf,
+    log: File,
+    policy: SyncPolicy,
+    unsynced: usize,
+    log_records: usize,
+    compact_after: Option<usize>,
+    compaction_error: Option<io::Error>,
+}
+
+// How one log record read back.
+enum Replay<K, V> {
+    Insert(K, V),
+    Remove(K),
+    // Cut short, or failing its checksum, at the very end of the log.
+    Torn,
+    Corrupt,
+}
+
+fn parse<K: Codec, V: Codec>(bytes: &[u8]) -> (Replay<K, V>, usize) {
+    let Some((header, rest)) = bytes.split_first_chunk::<8>() else {
+        return (Replay::Torn, 0);
+    };
+    let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+    let checksum = u32::from_le_bytes(header[4..].try_into().unwrap());
+    // A crash can leave the file extended with zeros its data never reached.
+    if bytes.iter().all(|&byte| byte == 0) {
+        return (Replay::Torn, 0);
+    }
+    // No record has an empty body. An interrupted append leaves only a part
+    // of one record, while a damaged length can reach past records that are
+    // still whole.
+    if len == 0 || rest.len() < len {
+        let torn = !(1..bytes.len()).any(|start| is_record(&bytes[start..]));
+        return (if torn { Replay::Torn } else { Replay::Corrupt }, 0);
+    }
+    let body = &rest[..len];
+    if crc32(body) != checksum {
+        let torn = rest.len() == len;
+        return (if torn { Replay::Torn } else { Replay::Corrupt }, 0);
+    }
+    let record = match body.split_first() {
+        Some((&INSERT, mut fields)) => match (take_field(&mut fields), take_field(&mut fields)) {
+            (Some(key), Some(value)) if fields.is_empty() => Replay::Insert(key, value),
+            _ => Replay::Corrupt,
+        },
+        Some((&REMOVE, mut fields)) => match take_field(&mut fields) {
+            Some(key) if fields.is_empty() => Replay::Remove(key),
+            _ => Replay::Corrupt,
+        },
+        _ => Replay::Corrupt,
+    };
+    (record, 8 + len)
+}
+
+// Whether a whole record with a matching checksum starts `bytes`.
+fn is_record(bytes: &[u8]) -> bool {
+    let Some((header, rest)) = bytes.split_first_chunk::<8>() else {
+        return false;
+    };
+    let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+    let checksum = u32::from_le_bytes(header[4..].try_into().unwrap());
+    match rest.get(..len) {
+        Some(body) => matches!(body.first(), Some(&INSERT | &REMOVE)) && crc32(body) == checksum,
+        None => false,
+    }
+}
+
+// Makes a rename or new file in `dir` durable.
+#[cfg(unix)]
+pub(crate) fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn sync_dir(_: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+impl<K: Ord + Codec, V: Codec, A: Augment<K, V>> DurableAVLMap<K, V, A> {
+    // Opens the map kept in `dir`, creating it if needed: loads the snapshot
+    // and replays the log over it. A torn record at the end of the log, left
+    // by a crash during an append, is cut off.
+    pub fn open<P: AsRef<Path>>(dir: P, policy: SyncPolicy) -> Result<Self, DurableError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        sync_dir(dir.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new(".")))?;
+        match fs::remove_file(dir.join(SNAPSHOT_TMP)) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error.into()),
+            _ => {}
+        }
+        let mut map = match File::open(dir.join(SNAPSHOT)) {
+            Ok(file) => AVLTreeMap::read_snapshot(BufReader::new(file))?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => AVLTreeMap::with_augment(),
+            Err(error) => return Err(error.into()),
+        };
+
+        let mut log = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(dir.join(LOG))?;
+        sync_dir(&dir)?;
+        let mut bytes = Vec::new();
+        log.read_to_end(&mut bytes)?;
+        let (mut offset, mut log_records) = (0, 0);
+        while offset < bytes.len() {
+            let (record, len) = parse(&bytes[offset..]);
+            match record {
+                Replay::Insert(key, value) => {
+                    map.insert(key, value);
+                }
+                Replay::Remove(key) => {
+                    map.remove(&key);
+                }
+                Replay::Torn => break,
+                Replay::Corrupt => return Err(DurableError::CorruptLog { offset: offset as u64 }),
+            }
+            offset += len;
+            log_records += 1;
+        }
+        if offset < bytes.len() {
+            log.set_len(offset as u64)?;
+            log.sync_all()?;
+        }
+        log.seek(SeekFrom::Start(offset as u64))?;
+        Ok(Self {
+            map,
+            dir,
+            log,
+            policy,
+            unsynced: 0,
+            log_records,
+            compact_after: None,
+            compaction_error: None,
+        })
+    }
+
+    // Compacts on its own once the log holds `records` changes, or never
+    // with `None`, which is the default. A failed automatic compaction does
+    // not fail the change that set it off, which is logged and applied by
+    // then; see `take_compaction_error`.
+    pub fn set_auto_compaction(&mut self, records: Option<usize>) {
+        self.compact_after = records;
+    }
+
+    pub fn map(&self) -> &AVLTreeMap<K, V, A> {
+        &self.map
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.map.get(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.map.contains_key(key)
+    }
+
+    // The error of the last automatic compaction that failed, if any since
+    // this was last called. The log still holds every change, and the next
+    // change tries again.
+    pub fn take_compaction_error(&mut self) -> Option<io::Error> {
+        self.compaction_error.take()
+    }
+
+    // Changes since the last compaction.
+    pub fn log_records(&self) -> usize {
+        self.log_records
+    }
+
+    // Logs the insert, then applies it.
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>, DurableError> {
+        let mut body = vec![INSERT];
+        put_field(&key, &mut body)?;
+        put_field(&value, &mut body)?;
+        self.append(&body)?;
+        let old = self.map.insert(key, value);
+        self.maybe_compact();
+        Ok(old)
+    }
+
+    // Logs the removal, if there is anything to remove, then applies it.
+    pub fn remove<Q>(&mut self, key: &Q) -> Result<Option<V>, DurableError>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        let Some((stored, _)) = self.map.get_key_value(key) else {
+            return Ok(None);
+        };
+        let mut body = vec![REMOVE];
+        put_field(stored, &mut body)?;
+        self.append(&body)?;
+        let old = self.map.remove(key);
+        self.maybe_compact();
+        Ok(old)
+    }
+
+    // Appends one record, or, if that fails, leaves the log as it was so
+    // the next append does not land after a partial record.
+    fn append(&mut self, body: &[u8]) -> io::Result<()> {
+        let len = u32::try_from(body.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "log record is over 4 GiB"))?;
+        let mut record = Vec::with_capacity(8 + body.len());
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(&crc32(body).to_le_bytes());
+        record.extend_from_slice(body);
+        let sync = match self.policy {
+            SyncPolicy::Always => true,
+            SyncPolicy::Every(n) => self.unsynced + 1 >= n,
+            SyncPolicy::Never => false,
+        };
+        let offset = self.log.stream_position()?;
+        let written = self.log.write_all(&record).and_then(|()| if sync { self.log.sync_data() } else { Ok(()) });
+        if let Err(error) = written {
+            self.log.set_len(offset)?;
+            self.log.seek(SeekFrom::Start(offset))?;
+            return Err(error);
+        }
+        self.log_records += 1;
+        self.unsynced = if sync { 0 } else { self.unsynced + 1 };
+        Ok(())
+    }
+
+    // Flushes every logged change to disk.
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.log.sync_data()?;
+        self.unsynced = 0;
+        Ok(())
+    }
+
+    fn maybe_compact(&mut self) {
+        match self.compact_after {
+            Some(records) if self.log_records >= records => {
+                if let Err(error) = self.compact() {
+                    self.compaction_error = Some(error);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Removes every entry, durably once it returns: an empty snapshot
+    // replaces the old one and the log is emptied, as in `compact`.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.map = AVLTreeMap::with_augment();
+        self.compact()
+    }
+
+    // Writes the whole map to a new snapshot, swaps it in with an atomic
+    // rename and empties the log. A crash before the rename keeps the old
+    // snapshot and log. A crash after it, before the log is emptied, replays
+    // the log over a snapshot that already has its changes, which ends in
+    // the same state, since every record sets or clears a key outright.
+    pub fn compact(&mut self) -> io::Result<()> {
+        let path = self.dir.join(SNAPSHOT_TMP);
+        let mut writer = BufWriter::new(File::create(&path)?);
+        self.map.write_snapshot(&mut writer)?;
+        writer.into_inner().map_err(|error| error.into_error())?.sync_all()?;
+        fs::rename(&path, self.dir.join(SNAPSHOT))?;
+        sync_dir(&self.dir)?;
+        self.log.set_len(0)?;
+        self.log.seek(SeekFrom::Start(0))?;
+        self.log.sync_all()?;
+        self.log_records = 0;
+        self.unsynced = 0;
+        Ok(())
+    }
+}
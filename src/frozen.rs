@@ -0,0 +1,187 @@
+#![forbid(unsafe_code)]
+
+use crate::augment::Augment;
+use crate::tree::AVLTreeMap;
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::iter::FusedIterator;
+use std::ops::{Bound, Range, RangeBounds};
+
+// A read-only copy of a map, its keys stored in one array in Eytzinger
+// order: slot `k` (counted from 1) has its children in slots `2k` and
+// `2k + 1`, so the top levels of every search share a few cache lines and
+// the descent is a loop without branches on the comparison. Values are kept
+// in key order, and each slot knows the rank of its key.
+pub struct FrozenMap<K, V> {
+    keys: Vec<K>,
+    ranks: Vec<usize>,
+    // The slot of each rank, to walk the keys in order.
+    slots: Vec<usize>,
+    values: Vec<V>,
+}
+
+// Numbers the slots of an implicit tree of `len` nodes in order.
+fn number_slots(slot: usize, len: usize, next: &mut usize, ranks: &mut [usize]) {
+    if slot > len {
+        return;
+    }
+    number_slots(2 * slot, len, next, ranks);
+    ranks[slot - 1] = *next;
+    *next += 1;
+    number_slots(2 * slot + 1, len, next, ranks);
+}
+
+impl<K: Ord + Clone, V: Clone, A: Augment<K, V>> AVLTreeMap<K, V, A> {
+    // Copies the entries into a `FrozenMap`, in O(n).
+    pub fn freeze(&self) -> FrozenMap<K, V> {
+        let len = self.len();
+        let mut ranks = vec![0; len];
+        number_slots(1, len, &mut 0, &mut ranks);
+        let mut slots = vec![0; len];
+        for (slot, rank) in ranks.iter().enumerate() {
+            slots[*rank] = slot;
+        }
+        let sorted: Vec<_> = self.iter().collect();
+        FrozenMap {
+            keys: ranks.iter().map(|rank| sorted[*rank].0.clone()).collect(),
+            values: sorted.iter().map(|(_, value)| (*value).clone()).collect(),
+            ranks,
+            slots,
+        }
+    }
+}
+
+impl<K: Ord, V> FrozenMap<K, V> {
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    // The number of keys below `key`, or up to and including it if
+    // `inclusive`.
+    fn count_below<Q>(&self, key: &Q, inclusive: bool) -> usize
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        let len = self.keys.len();
+        // Keys ordered below this count as below the boundary.
+        let boundary = if inclusive { Ordering::Greater } else { Ordering::Equal };
+        let mut slot = 1;
+        while slot <= len {
+            slot = 2 * slot + (self.keys[slot - 1].borrow().cmp(key) < boundary) as usize;
+        }
+        // Drop the trailing right turns and the last left turn, which leaves
+        // the first key past the boundary, or 0 if there is none.
+        slot >>= slot.trailing_ones() + 1;
+        match slot {
+            0 => len,
+            slot => self.ranks[slot - 1],
+        }
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        let rank = self.count_below(key, false);
+        self.nth_key_value(rank).filter(|(found, _)| (*found).borrow() == key)
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.get_key_value(key).map(|(_, value)| value)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.get_key_value(key).is_some()
+    }
+
+    pub fn nth_key_value(&self, index: usize) -> Option<(&K, &V)> {
+        let slot = *self.slots.get(index)?;
+        Some((&self.keys[slot], &self.values[index]))
+    }
+
+    // The number of keys below `key`, whether or not it is present.
+    pub fn rank<Q>(&self, key: &Q) -> usize
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.count_below(key, false)
+    }
+
+    pub fn iter(&self) -> FrozenIter<'_, K, V> {
+        FrozenIter {
+            map: self,
+            ranks: 0..self.len(),
+        }
+    }
+
+    pub fn range<Q, R>(&self, range: R) -> FrozenIter<'_, K, V>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+            R: RangeBounds<Q>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(key) => self.count_below(key, false),
+            Bound::Excluded(key) => self.count_below(key, true),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(key) => self.count_below(key, true),
+            Bound::Excluded(key) => self.count_below(key, false),
+            Bound::Unbounded => self.len(),
+        };
+        FrozenIter {
+            map: self,
+            ranks: start..end.max(start),
+        }
+    }
+
+    // Back to a tree, built bottom-up in O(n).
+    pub fn thaw(self) -> AVLTreeMap<K, V> {
+        let mut keys: Vec<_> = self.keys.into_iter().map(Some).collect();
+        let entries = self.slots.iter().map(|slot| keys[*slot].take().unwrap()).zip(self.values).collect();
+        AVLTreeMap::from_sorted_vec(entries)
+    }
+}
+
+pub struct FrozenIter<'a, K, V> {
+    map: &'a FrozenMap<K, V>,
+    ranks: Range<usize>,
+}
+
+impl<'a, K: Ord, V> Iterator for FrozenIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.map.nth_key_value(self.ranks.next()?)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.ranks.size_hint()
+    }
+}
+
+impl<'a, K: Ord, V> DoubleEndedIterator for FrozenIter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.map.nth_key_value(self.ranks.next_back()?)
+    }
+}
+
+impl<'a, K: Ord, V> ExactSizeIterator for FrozenIter<'a, K, V> {}
+
+impl<'a, K: Ord, V> FusedIterator for FrozenIter<'a, K, V> {}
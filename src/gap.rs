@@ -0,0 +1,159 @@
+#![forbid(unsafe_code)]
+
+use crate::augment::Augment;
+use crate::node::Link;
+use crate::tree::AVLTreeMap;
+use std::ops::{Bound, RangeBounds};
+
+// Keys that are whole numbers, so that the free values between two keys can
+// be counted. Every primitive integer up to 64 bits is one.
+pub trait IntegerKey: Ord + Copy {
+    const MIN: Self;
+    const MAX: Self;
+
+    fn to_i128(self) -> i128;
+
+    fn from_i128(value: i128) -> Option<Self>;
+}
+
+macro_rules! impl_integer_key {
+    ($($int:ty),*) => {
+        $(
+            impl IntegerKey for $int {
+                const MIN: Self = <$int>::MIN;
+                const MAX: Self = <$int>::MAX;
+
+                fn to_i128(self) -> i128 {
+                    self as i128
+                }
+
+                fn from_i128(value: i128) -> Option<Self> {
+                    Self::try_from(value).ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_key!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+// Keys are distinct integers in increasing order, so `key - rank` never
+// decreases along the ranks, and stays the same exactly as long as the keys
+// run without a gap. Counts the leading ranks where it is at most `limit`.
+fn run_end<K: IntegerKey, V, A: Augment<K, V>>(mut node: &Link<K, V, A>, limit: i128) -> usize {
+    let mut below = 0;
+    while let Some(node_ref) = node {
+        let rank = below + node_ref.left_count;
+        if node_ref.get_key().to_i128() - rank as i128 <= limit {
+            below = rank + 1;
+            node = node_ref.get_right();
+        } else {
+            node = node_ref.get_left();
+        }
+    }
+    below
+}
+
+impl<K: IntegerKey, V, A: Augment<K, V>> AVLTreeMap<K, V, A> {
+    // The smallest value at least `from` that is not a key, or `None` if every
+    // value from there up to `K::MAX` is taken.
+    pub fn first_gap_from(&self, from: K) -> Option<K> {
+        let start = self.rank_range(from..).start;
+        let offset = from.to_i128() - start as i128;
+        K::from_i128(offset + run_end(self.root(), offset) as i128)
+    }
+
+    // How many values within `range` are not keys.
+    pub fn count_missing<R: RangeBounds<K>>(&self, range: R) -> u128 {
+        let low = match range.start_bound() {
+            Bound::Included(low) => low.to_i128(),
+            Bound::Excluded(low) => low.to_i128() + 1,
+            Bound::Unbounded => K::MIN.to_i128(),
+        };
+        let high = match range.end_bound() {
+            Bound::Included(high) => high.to_i128(),
+            Bound::Excluded(high) => high.to_i128() - 1,
+            Bound::Unbounded => K::MAX.to_i128(),
+        };
+        if low > high {
+            return 0;
+        }
+        (high - low + 1) as u128 - self.rank_range(range).len() as u128
+    }
+}
+
+// Hands out the smallest free id at or above a starting point.
+pub struct IdAllocator<K: IntegerKey = u64> {
+    used: AVLTreeMap<K, ()>,
+    first: K,
+}
+
+impl<K: IntegerKey> Default for IdAllocator<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: IntegerKey> Clone for IdAllocator<K> {
+    fn clone(&self) -> Self {
+        Self {
+            used: self.used.clone(),
+            first: self.first,
+        }
+    }
+}
+
+impl<K: IntegerKey> IdAllocator<K> {
+    // Allocates from zero up.
+    pub fn new() -> Self {
+        Self::starting_at(K::from_i128(0).unwrap())
+    }
+
+    pub fn starting_at(first: K) -> Self {
+        Self {
+            used: AVLTreeMap::new(),
+            first,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.used.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.used.is_empty()
+    }
+
+    // The smallest free id, or `None` when all of them are taken.
+    pub fn allocate(&mut self) -> Option<K> {
+        self.allocate_from(self.first)
+    }
+
+    pub fn allocate_from(&mut self, from: K) -> Option<K> {
+        let id = self.used.first_gap_from(from.max(self.first))?;
+        self.used.insert(id, ());
+        Some(id)
+    }
+
+    // Marks `id` as taken. Returns whether it was free.
+    pub fn reserve(&mut self, id: K) -> bool {
+        self.used.insert(id, ()).is_none()
+    }
+
+    // Frees `id`. Returns whether it was taken.
+    pub fn release(&mut self, id: K) -> bool {
+        self.used.remove(&id).is_some()
+    }
+
+    pub fn is_allocated(&self, id: K) -> bool {
+        self.used.contains_key(&id)
+    }
+
+    pub fn count_free<R: RangeBounds<K>>(&self, range: R) -> u128 {
+        self.used.count_missing(range)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = K> + '_ {
+        self.used.iter().map(|(id, _)| *id)
+    }
+}
@@ -0,0 +1,274 @@
+#![forbid(unsafe_code)]
+
+// A sorted image of a map with fixed-size keys and values, to be queried
+// in place from a byte slice, such as a memory-mapped file, without
+// deserializing it. All integers are little-endian.
+//
+//   header   magic "AVLTFIX1", key size: u32, value size: u32, entry count: u64, reserved: u64
+//   keys     entry count keys in increasing order, zero-padded to a multiple of 8 bytes
+//   values   entry count values, in the order of their keys
+//
+// Both arrays start at multiples of 8 bytes.
+
+use crate::augment::Augment;
+use crate::tree::AVLTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::ops::{Bound, Range, RangeBounds};
+
+const MAGIC: [u8; 8] = *b"AVLTFIX1";
+const HEADER_LEN: usize = 32;
+
+// Types stored as exactly `SIZE` bytes.
+pub trait FixedSize: Sized {
+    const SIZE: usize;
+
+    // Writes `SIZE` bytes to `out`, which is that long.
+    fn write_le(&self, out: &mut [u8]);
+
+    // Reads back what `write_le` wrote.
+    fn read_le(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_fixed_size {
+    ($($int:ty),*) => {
+        $(
+            impl FixedSize for $int {
+                const SIZE: usize = size_of::<$int>();
+
+                fn write_le(&self, out: &mut [u8]) {
+                    out.copy_from_slice(&self.to_le_bytes());
+                }
+
+                fn read_le(bytes: &[u8]) -> Self {
+                    Self::from_le_bytes(bytes.try_into().unwrap())
+                }
+            }
+        )*
+    };
+}
+
+impl_fixed_size!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128, f32, f64);
+
+impl<const N: usize> FixedSize for [u8; N] {
+    const SIZE: usize = N;
+
+    fn write_le(&self, out: &mut [u8]) {
+        out.copy_from_slice(self);
+    }
+
+    fn read_le(bytes: &[u8]) -> Self {
+        bytes.try_into().unwrap()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ImageError {
+    BadMagic,
+    // The image was written for other key or value sizes.
+    SizeMismatch { key: u32, value: u32 },
+    Truncated,
+    // Found by `ImageView::check_sorted`.
+    Unsorted { entry: u64 },
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic => f.write_str("not a map image"),
+            Self::SizeMismatch { key, value } => {
+                write!(f, "image holds {key}-byte keys and {value}-byte values")
+            }
+            Self::Truncated => f.write_str("map image is truncated"),
+            Self::Unsorted { entry } => write!(f, "entry {entry} is out of key order"),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+impl<K: Ord + FixedSize, V: FixedSize, A: Augment<K, V>> AVLTreeMap<K, V, A> {
+    // Writes the image format above, which `ImageView` reads.
+    pub fn write_image<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut header = [0; HEADER_LEN];
+        header[..8].copy_from_slice(&MAGIC);
+        header[8..12].copy_from_slice(&(K::SIZE as u32).to_le_bytes());
+        header[12..16].copy_from_slice(&(V::SIZE as u32).to_le_bytes());
+        header[16..24].copy_from_slice(&(self.len() as u64).to_le_bytes());
+        writer.write_all(&header)?;
+        let mut buffer = vec![0; K::SIZE.max(V::SIZE)];
+        for (key, _) in self {
+            key.write_le(&mut buffer[..K::SIZE]);
+            writer.write_all(&buffer[..K::SIZE])?;
+        }
+        let keys_len = self.len() * K::SIZE;
+        writer.write_all(&[0; 8][..keys_len.next_multiple_of(8) - keys_len])?;
+        for (_, value) in self {
+            value.write_le(&mut buffer[..V::SIZE]);
+            writer.write_all(&buffer[..V::SIZE])?;
+        }
+        Ok(())
+    }
+}
+
+// A map image read in place. Opening one checks only the header and the
+// length, in O(1), and lookups trust the keys to be sorted as written;
+// `check_sorted` verifies that in O(n) for images of unknown origin.
+pub struct ImageView<'a, K, V> {
+    keys: &'a [u8],
+    values: &'a [u8],
+    len: usize,
+    _entries: PhantomData<(K, V)>,
+}
+
+impl<'a, K, V> Clone for ImageView<'a, K, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, K, V> Copy for ImageView<'a, K, V> {}
+
+impl<'a, K: Ord + FixedSize, V: FixedSize> ImageView<'a, K, V> {
+    pub fn new(bytes: &'a [u8]) -> Result<Self, ImageError> {
+        let header = bytes.get(..HEADER_LEN).ok_or(ImageError::Truncated)?;
+        if header[..8] != MAGIC {
+            return Err(ImageError::BadMagic);
+        }
+        let key = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        let value = u32::from_le_bytes(header[12..16].try_into().unwrap());
+        if key as usize != K::SIZE || value as usize != V::SIZE {
+            return Err(ImageError::SizeMismatch { key, value });
+        }
+        let len = usize::try_from(u64::from_le_bytes(header[16..24].try_into().unwrap()))
+            .map_err(|_| ImageError::Truncated)?;
+        let sizes = len.checked_mul(K::SIZE).zip(len.checked_mul(V::SIZE));
+        let (keys_len, values_len) = sizes.ok_or(ImageError::Truncated)?;
+        let body = &bytes[HEADER_LEN..];
+        let values_start = keys_len.checked_next_multiple_of(8).ok_or(ImageError::Truncated)?;
+        let end = values_start.checked_add(values_len).ok_or(ImageError::Truncated)?;
+        if body.len() < end {
+            return Err(ImageError::Truncated);
+        }
+        Ok(Self {
+            keys: &body[..keys_len],
+            values: &body[values_start..end],
+            len,
+            _entries: PhantomData,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn key(&self, index: usize) -> K {
+        K::read_le(&self.keys[index * K::SIZE..(index + 1) * K::SIZE])
+    }
+
+    fn value(&self, index: usize) -> V {
+        V::read_le(&self.values[index * V::SIZE..(index + 1) * V::SIZE])
+    }
+
+    // The number of keys below `key`, or up to and including it if
+    // `inclusive`, by binary search.
+    fn count_below(&self, key: &K, inclusive: bool) -> usize {
+        let (mut low, mut high) = (0, self.len);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            let found = self.key(middle);
+            if found < *key || (inclusive && found == *key) {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        low
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let index = self.count_below(key, false);
+        (index < self.len && self.key(index) == *key).then(|| self.value(index))
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        let index = self.count_below(key, false);
+        index < self.len && self.key(index) == *key
+    }
+
+    pub fn nth(&self, index: usize) -> Option<(K, V)> {
+        (index < self.len).then(|| (self.key(index), self.value(index)))
+    }
+
+    // The number of keys below `key`, whether or not it is present.
+    pub fn rank_of(&self, key: &K) -> usize {
+        self.count_below(key, false)
+    }
+
+    pub fn iter(&self) -> ImageIter<'a, K, V> {
+        ImageIter {
+            view: *self,
+            ranks: 0..self.len,
+        }
+    }
+
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> ImageIter<'a, K, V> {
+        let start = match range.start_bound() {
+            Bound::Included(key) => self.count_below(key, false),
+            Bound::Excluded(key) => self.count_below(key, true),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(key) => self.count_below(key, true),
+            Bound::Excluded(key) => self.count_below(key, false),
+            Bound::Unbounded => self.len,
+        };
+        ImageIter {
+            view: *self,
+            ranks: start..end.max(start),
+        }
+    }
+
+    pub fn check_sorted(&self) -> Result<(), ImageError> {
+        for index in 1..self.len {
+            if self.key(index - 1) >= self.key(index) {
+                return Err(ImageError::Unsorted { entry: index as u64 });
+            }
+        }
+        Ok(())
+    }
+}
+
+// Entries of an image, decoded as they are reached.
+pub struct ImageIter<'a, K, V> {
+    view: ImageView<'a, K, V>,
+    ranks: Range<usize>,
+}
+
+impl<'a, K: Ord + FixedSize, V: FixedSize> Iterator for ImageIter<'a, K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.view.nth(self.ranks.next()?)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.ranks.size_hint()
+    }
+}
+
+impl<'a, K: Ord + FixedSize, V: FixedSize> DoubleEndedIterator for ImageIter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.view.nth(self.ranks.next_back()?)
+    }
+}
+
+impl<'a, K: Ord + FixedSize, V: FixedSize> ExactSizeIterator for ImageIter<'a, K, V> {}
+
+impl<'a, K: Ord + FixedSize, V: FixedSize> FusedIterator for ImageIter<'a, K, V> {}
@@ -0,0 +1,147 @@
+#![forbid(unsafe_code)]
+
+use crate::augment::Augment;
+use crate::node::{Link, Node};
+use crate::tree::AVLTreeMap;
+use std::ops::Bound;
+
+// Intervals are half-open, `[start, end)`. Equal intervals are told apart by
+// the order they were inserted in, so the tree key is `(start, end, seq)`.
+type Key<T> = (T, T, u64);
+
+struct MaxEnd;
+
+impl<T: Ord + Clone, V> Augment<Key<T>, V> for MaxEnd {
+    type Summary = Option<T>;
+
+    fn identity() -> Option<T> {
+        None
+    }
+
+    fn lift(key: &Key<T>, _: &V) -> Option<T> {
+        Some(key.1.clone())
+    }
+
+    fn combine(left: &Option<T>, right: &Option<T>) -> Option<T> {
+        match (left, right) {
+            (Some(l), Some(r)) => Some(l.max(r).clone()),
+            (Some(l), None) => Some(l.clone()),
+            (None, r) => r.clone(),
+        }
+    }
+}
+
+pub struct IntervalTreeMap<T: Ord + Clone, V> {
+    map: AVLTreeMap<Key<T>, V, MaxEnd>,
+    next_seq: u64,
+}
+
+impl<T: Ord + Clone, V> Default for IntervalTreeMap<T, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord + Clone, V: Clone> Clone for IntervalTreeMap<T, V> {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+            next_seq: self.next_seq,
+        }
+    }
+}
+
+impl<T: Ord + Clone, V> IntervalTreeMap<T, V> {
+    pub fn new() -> Self {
+        Self {
+            map: AVLTreeMap::with_augment(),
+            next_seq: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn insert(&mut self, start: T, end: T, value: V) {
+        assert!(start <= end, "interval start is greater than its end");
+        self.map.insert((start, end, self.next_seq), value);
+        self.next_seq += 1;
+    }
+
+    // Removes the earliest inserted copy of `[start, end)`.
+    pub fn remove(&mut self, start: &T, end: &T) -> Option<V> {
+        let first = (start.clone(), end.clone(), 0);
+        let key = match self.map.ceiling_key_value(&first) {
+            Some((key, _)) if key.0 == *start && key.1 == *end => key.clone(),
+            _ => return None,
+        };
+        self.map.remove(&key)
+    }
+
+    pub fn overlapping(&self, start: T, end: T) -> Overlapping<'_, T, V> {
+        Overlapping::new(self.map.root(), start, Bound::Excluded(end))
+    }
+
+    pub fn containing(&self, point: T) -> Overlapping<'_, T, V> {
+        Overlapping::new(self.map.root(), point.clone(), Bound::Included(point))
+    }
+}
+
+// In-order walk yielding intervals with `end > after` and a start inside
+// `before`. Subtrees whose max end is not past `after` are never entered, and
+// nothing right of a start beyond `before` is.
+pub struct Overlapping<'a, T: Ord + Clone, V> {
+    stack: Vec<&'a Node<Key<T>, V, MaxEnd>>,
+    after: T,
+    before: Bound<T>,
+}
+
+impl<'a, T: Ord + Clone, V> Overlapping<'a, T, V> {
+    fn new(root: &'a Link<Key<T>, V, MaxEnd>, after: T, before: Bound<T>) -> Self {
+        let mut iter = Self {
+            stack: Vec::new(),
+            after,
+            before,
+        };
+        iter.push_left(root);
+        iter
+    }
+
+    fn push_left(&mut self, mut node: &'a Link<Key<T>, V, MaxEnd>) {
+        while let Some(node_ref) = node {
+            if node_ref.summary().as_ref().is_none_or(|end| *end <= self.after) {
+                break;
+            }
+            let start = &node_ref.get_key().0;
+            let starts_before = match &self.before {
+                Bound::Included(before) => start <= before,
+                Bound::Excluded(before) => start < before,
+                Bound::Unbounded => true,
+            };
+            if starts_before {
+                self.stack.push(node_ref);
+            }
+            node = node_ref.get_left();
+        }
+    }
+}
+
+impl<'a, T: Ord + Clone, V> Iterator for Overlapping<'a, T, V> {
+    type Item = (&'a T, &'a T, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            self.push_left(node.get_right());
+            let ((start, end, _), value) = node.get_key_value();
+            if *end > self.after {
+                return Some((start, end, value));
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,70 @@
+#![forbid(unsafe_code)]
+
+use crate::augment::Augment;
+use crate::node::{Link, Node};
+use std::iter::FusedIterator;
+
+// In-order walk over the ranks `[start, start + len)`. The stack holds the
+// nodes still to be visited whose left subtrees are already done.
+pub struct Iter<'a, K, V, A: Augment<K, V> = ()> {
+    stack: Vec<&'a Node<K, V, A>>,
+    remaining: usize,
+}
+
+impl<'a, K: Ord, V, A: Augment<K, V>> Iter<'a, K, V, A> {
+    pub(crate) fn new(root: &'a Link<K, V, A>, mut start: usize, len: usize) -> Self {
+        let mut stack = Vec::new();
+        let mut node = root;
+        while let Some(node_ref) = node {
+            if start <= node_ref.left_count {
+                stack.push(node_ref.as_ref());
+                if start == node_ref.left_count {
+                    break;
+                }
+                node = node_ref.get_left();
+            } else {
+                start -= node_ref.left_count + 1;
+                node = node_ref.get_right();
+            }
+        }
+        Self {
+            stack,
+            remaining: len,
+        }
+    }
+}
+
+impl<'a, K, V, A: Augment<K, V>> Clone for Iter<'a, K, V, A> {
+    fn clone(&self) -> Self {
+        Self {
+            stack: self.stack.clone(),
+            remaining: self.remaining,
+        }
+    }
+}
+
+impl<'a, K: Ord, V, A: Augment<K, V>> Iterator for Iter<'a, K, V, A> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = self.stack.pop()?;
+        let mut child = node.get_right();
+        while let Some(child_ref) = child {
+            self.stack.push(child_ref);
+            child = child_ref.get_left();
+        }
+        self.remaining -= 1;
+        Some(node.get_key_value())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K: Ord, V, A: Augment<K, V>> ExactSizeIterator for Iter<'a, K, V, A> {}
+
+impl<'a, K: Ord, V, A: Augment<K, V>> FusedIterator for Iter<'a, K, V, A> {}
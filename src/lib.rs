@@ -1,3 +1,58 @@
+mod augment;
+mod concurrent;
+mod durable;
+mod frozen;
+mod gap;
+mod image;
+mod interval;
+mod iter;
+mod lsm;
+mod multi;
 mod node;
+mod page;
+#[cfg(feature = "rayon")]
+mod par;
+mod partition;
+mod priority_queue;
+mod publish;
+mod range_set;
+mod rope;
+mod salvage;
+#[cfg(feature = "rand")]
+mod sample;
+#[cfg(feature = "serde")]
+mod serialize;
+mod snapshot;
+mod sorted_set;
+mod transaction;
 mod tree;
+mod vec;
+mod versioned;
+mod window;
+pub use augment::Augment;
+pub use concurrent::{ConcurrentAVLMap, RangeSnapshot, RangeSnapshotIter};
+pub use durable::{DurableAVLMap, DurableError, SyncPolicy};
+pub use frozen::{FrozenIter, FrozenMap};
+pub use gap::{IdAllocator, IntegerKey};
+pub use image::{FixedSize, ImageError, ImageIter, ImageView};
+pub use interval::{IntervalTreeMap, Overlapping};
+pub use iter::Iter;
+pub use lsm::{LsmError, LsmStore};
+pub use multi::{AVLMultiMap, AVLMultiSet};
+pub use page::Page;
+#[cfg(feature = "rayon")]
+pub use par::{ParIter, ParIterMut};
+pub use partition::RankView;
+pub use priority_queue::{AVLPriorityQueue, Handle};
+pub use publish::{Publisher, Reader};
+pub use range_set::{AVLRangeMap, AVLRangeSet, Gaps, Point, RangeIter};
+pub use rope::{AVLRope, Chunks};
+#[cfg(feature = "serde")]
+pub use serialize::ShapePreserving;
+pub use snapshot::{Codec, SnapshotError};
+pub use sorted_set::{AVLSortedSet, SortedSetIter};
+pub use transaction::Transaction;
 pub use tree::AVLTreeMap;
+pub use vec::{AVLVec, VecIter};
+pub use versioned::VersionedAVLMap;
+pub use window::AVLRollingWindow;
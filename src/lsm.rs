@@ -0,0 +1,594 @@
+#![forbid(unsafe_code)]
+
+// A small log-structured store. Writes go to a memtable, an `AVLTreeMap`
+// made durable by `DurableAVLMap`, which is flushed into an immutable sorted
+// run file once it is large enough. Reads look at the memtable and then at
+// the runs, newest first. Removals are written as tombstones, which shadow
+// older values until compaction merges every run into one and drops them.
+//
+// A run file, integers little-endian:
+//
+//   header   magic "AVLTRUN1", flags: u8 (1 if the run replaces all older runs)
+//   records  length: u32, key field, value field (an `Option`, `None` for a tombstone)
+//   footer   record count: u64, CRC-32 of the records: u32, magic "AVLTRUNF"
+//
+// Fields are length-prefixed `Codec` encodings, and keys strictly increase.
+
+use crate::durable::{sync_dir, DurableAVLMap, DurableError, SyncPolicy};
+use crate::snapshot::{crc32, crc32_extend, put_field, take_field, Codec};
+use std::cmp::Reverse;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
+
+const MAGIC: [u8; 8] = *b"AVLTRUN1";
+const FOOTER_MAGIC: [u8; 8] = *b"AVLTRUNF";
+const HEADER_LEN: u64 = 9;
+const FOOTER_LEN: u64 = 20;
+const BASE: u8 = 1;
+// Every this many records a run keeps the key and offset in its index.
+const INDEX_EVERY: usize = 32;
+// How much of a run file is read at once.
+const READ_CHUNK: usize = 1 << 16;
+const MEMTABLE: &str = "memtable";
+
+#[derive(Debug)]
+pub enum LsmError {
+    Io(io::Error),
+    Durable(DurableError),
+    CorruptRun { path: PathBuf },
+}
+
+impl fmt::Display for LsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "store I/O failed: {error}"),
+            Self::Durable(error) => error.fmt(f),
+            Self::CorruptRun { path } => write!(f, "run file {} is corrupt", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for LsmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Durable(error) => Some(error),
+            Self::CorruptRun { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for LsmError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<DurableError> for LsmError {
+    fn from(error: DurableError) -> Self {
+        Self::Durable(error)
+    }
+}
+
+// An open run file with its sparse index. The file stays open, so readers
+// can finish with a run that compaction has already deleted.
+struct Run<K> {
+    seq: u64,
+    path: PathBuf,
+    file: Mutex<File>,
+    data_end: u64,
+    index: Vec<(K, u64)>,
+    last: Option<K>,
+    base: bool,
+}
+
+fn run_path(dir: &Path, seq: u64) -> PathBuf {
+    dir.join(format!("run-{seq:020}"))
+}
+
+// Splits the record at the front of `bytes` into its body and the rest.
+fn split_record(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (len, rest) = bytes.split_first_chunk::<4>()?;
+    let len = u32::from_le_bytes(*len) as usize;
+    (rest.len() >= len).then(|| rest.split_at(len))
+}
+
+fn decode_record<K: Codec, V: Codec>(mut body: &[u8]) -> Option<(K, Option<V>)> {
+    let key = take_field(&mut body)?;
+    let value = take_field(&mut body)?;
+    body.is_empty().then_some((key, value))
+}
+
+impl<K: Ord + Clone + Codec> Run<K> {
+    // Reads the whole file once to check it and to build the index.
+    fn open(path: PathBuf, seq: u64) -> Result<Self, LsmError> {
+        let corrupt = || LsmError::CorruptRun { path: path.clone() };
+        let mut file = File::open(&path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        if (bytes.len() as u64) < HEADER_LEN + FOOTER_LEN || bytes[..8] != MAGIC {
+            return Err(corrupt());
+        }
+        let (data, footer) = bytes[HEADER_LEN as usize..].split_at(bytes.len() - (HEADER_LEN + FOOTER_LEN) as usize);
+        let count = u64::from_le_bytes(footer[..8].try_into().unwrap());
+        let checksum = u32::from_le_bytes(footer[8..12].try_into().unwrap());
+        if footer[12..] != FOOTER_MAGIC || crc32(data) != checksum {
+            return Err(corrupt());
+        }
+        let (mut index, mut last, mut found) = (Vec::new(), None::<K>, 0u64);
+        let mut rest = data;
+        while !rest.is_empty() {
+            let offset = HEADER_LEN + (data.len() - rest.len()) as u64;
+            let (mut body, tail) = split_record(rest).ok_or_else(corrupt)?;
+            let key: K = take_field(&mut body).ok_or_else(corrupt)?;
+            if last.as_ref().is_some_and(|last| *last >= key) {
+                return Err(corrupt());
+            }
+            if found.is_multiple_of(INDEX_EVERY as u64) {
+                index.push((key.clone(), offset));
+            }
+            last = Some(key);
+            found += 1;
+            rest = tail;
+        }
+        if found != count {
+            return Err(corrupt());
+        }
+        Ok(Self {
+            seq,
+            path,
+            file: Mutex::new(file),
+            data_end: HEADER_LEN + data.len() as u64,
+            index,
+            last,
+            base: bytes[8] == BASE,
+        })
+    }
+
+    // Where to start reading for keys from `start` on.
+    fn seek(&self, start: Bound<&K>) -> u64 {
+        let position = match start {
+            Bound::Included(start) | Bound::Excluded(start) => self.index.partition_point(|(key, _)| key <= start),
+            Bound::Unbounded => 0,
+        };
+        position.checked_sub(1).map_or(HEADER_LEN, |position| self.index[position].1)
+    }
+
+    fn get<V: Codec>(self: &Arc<Self>, key: &K) -> Result<Option<Option<V>>, LsmError> {
+        if self.last.as_ref().is_none_or(|last| key > last) || *key < self.index[0].0 {
+            return Ok(None);
+        }
+        let mut reader = RunReader::new(self.clone(), self.seek(Bound::Included(key)));
+        for _ in 0..INDEX_EVERY {
+            match reader.next()? {
+                Some((found, value)) if found == *key => return Ok(Some(value)),
+                Some((found, _)) if found < *key => {}
+                _ => break,
+            }
+        }
+        Ok(None)
+    }
+}
+
+// Reads the records of a run in order from some offset, a chunk at a time.
+struct RunReader<K, V> {
+    run: Arc<Run<K>>,
+    // File offset of `buffer[0]`.
+    offset: u64,
+    buffer: Vec<u8>,
+    position: usize,
+    _value: PhantomData<V>,
+}
+
+impl<K: Codec, V: Codec> RunReader<K, V> {
+    fn new(run: Arc<Run<K>>, offset: u64) -> Self {
+        Self {
+            run,
+            offset,
+            buffer: Vec::new(),
+            position: 0,
+            _value: PhantomData,
+        }
+    }
+
+    fn corrupt(&self) -> LsmError {
+        LsmError::CorruptRun {
+            path: self.run.path.clone(),
+        }
+    }
+
+    // Makes sure the next `len` bytes are in the buffer.
+    fn fill(&mut self, len: usize) -> Result<(), LsmError> {
+        if self.buffer.len() - self.position >= len {
+            return Ok(());
+        }
+        let start = self.offset + self.position as u64;
+        if self.run.data_end - start < len as u64 {
+            return Err(self.corrupt());
+        }
+        let read = (self.run.data_end - start).min(len.max(READ_CHUNK) as u64) as usize;
+        self.buffer.resize(read, 0);
+        let mut file = self.run.file.lock().unwrap();
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut self.buffer)?;
+        (self.offset, self.position) = (start, 0);
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<(K, Option<V>)>, LsmError> {
+        if self.offset + self.position as u64 >= self.run.data_end {
+            return Ok(None);
+        }
+        self.fill(4)?;
+        let len = u32::from_le_bytes(self.buffer[self.position..self.position + 4].try_into().unwrap()) as usize;
+        self.fill(4 + len)?;
+        let body = &self.buffer[self.position + 4..self.position + 4 + len];
+        let record = decode_record(body).ok_or_else(|| self.corrupt())?;
+        self.position += 4 + len;
+        Ok(Some(record))
+    }
+}
+
+// Writes a new run to a temporary file and renames it into place.
+struct RunWriter<K> {
+    path: PathBuf,
+    file: BufWriter<File>,
+    offset: u64,
+    checksum: u32,
+    index: Vec<(K, u64)>,
+    last: Option<K>,
+    count: u64,
+    base: bool,
+    record: Vec<u8>,
+}
+
+impl<K: Ord + Clone + Codec> RunWriter<K> {
+    fn create(path: PathBuf, base: bool) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path.with_extension("tmp"))?);
+        file.write_all(&MAGIC)?;
+        file.write_all(&[if base { BASE } else { 0 }])?;
+        Ok(Self {
+            path,
+            file,
+            offset: HEADER_LEN,
+            checksum: 0,
+            index: Vec::new(),
+            last: None,
+            count: 0,
+            base,
+            record: Vec::new(),
+        })
+    }
+
+    fn push<V: Codec>(&mut self, key: K, value: &Option<V>) -> io::Result<()> {
+        self.record.clear();
+        self.record.extend_from_slice(&[0; 4]);
+        put_field(&key, &mut self.record)?;
+        put_field(value, &mut self.record)?;
+        let len = (self.record.len() - 4) as u32;
+        self.record[..4].copy_from_slice(&len.to_le_bytes());
+        self.file.write_all(&self.record)?;
+        self.checksum = crc32_extend(self.checksum, &self.record);
+        if self.count.is_multiple_of(INDEX_EVERY as u64) {
+            self.index.push((key.clone(), self.offset));
+        }
+        self.offset += self.record.len() as u64;
+        self.last = Some(key);
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self, seq: u64) -> io::Result<Run<K>> {
+        self.file.write_all(&self.count.to_le_bytes())?;
+        self.file.write_all(&self.checksum.to_le_bytes())?;
+        self.file.write_all(&FOOTER_MAGIC)?;
+        let file = self.file.into_inner().map_err(|error| error.into_error())?;
+        file.sync_all()?;
+        fs::rename(self.path.with_extension("tmp"), &self.path)?;
+        sync_dir(self.path.parent().unwrap())?;
+        Ok(Run {
+            seq,
+            file: Mutex::new(File::open(&self.path)?),
+            path: self.path,
+            data_end: self.offset,
+            index: self.index,
+            last: self.last,
+            base: self.base,
+        })
+    }
+}
+
+enum Source<K, V, I> {
+    Memtable(I),
+    Run(RunReader<K, V>),
+}
+
+impl<K: Codec, V: Codec, I: Iterator<Item = (K, Option<V>)>> Source<K, V, I> {
+    fn next(&mut self) -> Result<Option<(K, Option<V>)>, LsmError> {
+        match self {
+            Self::Memtable(entries) => Ok(entries.next()),
+            Self::Run(reader) => reader.next(),
+        }
+    }
+}
+
+// Merges sorted sources, the newest first, and hands `emit` each key in
+// order with its newest value, until `past_end` says to stop.
+fn merge<K, V, I, P, E>(mut sources: Vec<Source<K, V, I>>, past_end: P, mut emit: E) -> Result<(), LsmError>
+    where
+        K: Ord + Codec,
+        V: Codec,
+        I: Iterator<Item = (K, Option<V>)>,
+        P: Fn(&K) -> bool,
+        E: FnMut(K, Option<V>) -> Result<(), LsmError>,
+{
+    let mut heads = sources.iter_mut().map(Source::next).collect::<Result<Vec<_>, _>>()?;
+    loop {
+        let mut newest: Option<usize> = None;
+        for (index, head) in heads.iter().enumerate() {
+            if let Some((key, _)) = head {
+                if newest.is_none_or(|newest| *key < heads[newest].as_ref().unwrap().0) {
+                    newest = Some(index);
+                }
+            }
+        }
+        let Some(newest) = newest else {
+            return Ok(());
+        };
+        let (key, value) = heads[newest].take().unwrap();
+        if past_end(&key) {
+            return Ok(());
+        }
+        heads[newest] = sources[newest].next()?;
+        for index in newest + 1..heads.len() {
+            if heads[index].as_ref().is_some_and(|(other, _)| *other == key) {
+                heads[index] = sources[index].next()?;
+            }
+        }
+        emit(key, value)?;
+    }
+}
+
+struct Shared<K> {
+    dir: PathBuf,
+    // Newest first.
+    runs: RwLock<Vec<Arc<Run<K>>>>,
+}
+
+// Merges every run present when it starts into one base run, dropping the
+// tombstones, as nothing older is left for them to shadow. The result takes
+// the place, and the number, of the newest run it merged, so runs flushed in
+// the meantime stay newer.
+fn compact_runs<K, V>(shared: &Shared<K>) -> Result<(), LsmError>
+    where
+        K: Ord + Clone + Codec,
+        V: Codec,
+{
+    let inputs = shared.runs.read().unwrap().clone();
+    if inputs.len() < 2 {
+        return Ok(());
+    }
+    let seq = inputs[0].seq;
+    let sources: Vec<Source<K, V, std::iter::Empty<_>>> =
+        inputs.iter().map(|run| Source::Run(RunReader::new(run.clone(), HEADER_LEN))).collect();
+    let mut writer = RunWriter::create(run_path(&shared.dir, seq), true)?;
+    merge(sources, |_| false, |key, value| match value {
+        Some(_) => Ok(writer.push(key, &value)?),
+        None => Ok(()),
+    })?;
+    let merged = Arc::new(writer.finish(seq)?);
+    let mut runs = shared.runs.write().unwrap();
+    runs.retain(|run| !inputs.iter().any(|input| input.seq == run.seq));
+    runs.push(merged);
+    drop(runs);
+    for input in &inputs[1..] {
+        fs::remove_file(&input.path)?;
+    }
+    Ok(())
+}
+
+// An embedded key-value store on a directory of its own.
+pub struct LsmStore<K, V>
+    where
+        K: Ord + Clone + Codec + Send + Sync + 'static,
+        V: Clone + Codec + Send + Sync + 'static,
+{
+    memtable: DurableAVLMap<K, Option<V>>,
+    shared: Arc<Shared<K>>,
+    next_seq: u64,
+    memtable_limit: usize,
+    compact_at: Option<usize>,
+    compaction: Option<JoinHandle<Result<(), LsmError>>>,
+    _value: PhantomData<V>,
+}
+
+impl<K, V> LsmStore<K, V>
+    where
+        K: Ord + Clone + Codec + Send + Sync + 'static,
+        V: Clone + Codec + Send + Sync + 'static,
+{
+    // Opens the store in `dir`, creating it if needed. The memtable log is
+    // synced as `policy` says. Flushes happen at 4096 memtable entries and
+    // compactions start in the background at 4 runs until set otherwise.
+    pub fn open<P: AsRef<Path>>(dir: P, policy: SyncPolicy) -> Result<Self, LsmError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let mut runs = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            let Some(seq) = name.strip_prefix("run-") else {
+                continue;
+            };
+            match seq.parse() {
+                Ok(seq) => runs.push(Run::open(path, seq)?),
+                // Left over from a flush or a compaction that did not finish.
+                Err(_) if path.extension().is_some_and(|extension| extension == "tmp") => fs::remove_file(&path)?,
+                Err(_) => {}
+            }
+        }
+        runs.sort_by_key(|run| Reverse(run.seq));
+        // Runs older than a base run were merged into it by a compaction that
+        // stopped before deleting them.
+        if let Some(base) = runs.iter().position(|run| run.base) {
+            for run in runs.drain(base + 1..) {
+                fs::remove_file(&run.path)?;
+            }
+        }
+        let next_seq = runs.first().map_or(0, |run| run.seq + 1);
+        Ok(Self {
+            memtable: DurableAVLMap::open(dir.join(MEMTABLE), policy)?,
+            shared: Arc::new(Shared {
+                dir,
+                runs: RwLock::new(runs.into_iter().map(Arc::new).collect()),
+            }),
+            next_seq,
+            memtable_limit: 1 << 12,
+            compact_at: Some(4),
+            compaction: None,
+            _value: PhantomData,
+        })
+    }
+
+    pub fn set_memtable_limit(&mut self, entries: usize) {
+        assert!(entries > 0, "memtable limit should be positive");
+        self.memtable_limit = entries;
+    }
+
+    // Starts a background compaction whenever a flush leaves `runs` runs, or
+    // never with `None`.
+    pub fn set_compaction_trigger(&mut self, runs: Option<usize>) {
+        self.compact_at = runs;
+    }
+
+    pub fn run_count(&self) -> usize {
+        self.shared.runs.read().unwrap().len()
+    }
+
+    pub fn memtable_len(&self) -> usize {
+        self.memtable.len()
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Result<(), LsmError> {
+        self.memtable.insert(key, Some(value))?;
+        self.maybe_flush()
+    }
+
+    // Writes a tombstone, which hides any older value of `key`.
+    pub fn remove(&mut self, key: K) -> Result<(), LsmError> {
+        self.memtable.insert(key, None)?;
+        self.maybe_flush()
+    }
+
+    pub fn get(&self, key: &K) -> Result<Option<V>, LsmError> {
+        if let Some(value) = self.memtable.get(key) {
+            return Ok(value.clone());
+        }
+        let runs = self.shared.runs.read().unwrap().clone();
+        for run in &runs {
+            if let Some(value) = run.get(key)? {
+                return Ok(value);
+            }
+        }
+        Ok(None)
+    }
+
+    // The live entries within `range`, in key order.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Result<Vec<(K, V)>, LsmError> {
+        let bounds = (range.start_bound(), range.end_bound());
+        let runs = self.shared.runs.read().unwrap().clone();
+        let memtable = self.memtable.map().range(bounds).map(|(key, value)| (key.clone(), value.clone()));
+        let mut sources = vec![Source::Memtable(memtable)];
+        for run in &runs {
+            sources.push(Source::Run(RunReader::new(run.clone(), run.seek(bounds.0))));
+        }
+        let past_end = |key: &K| match bounds.1 {
+            Bound::Included(end) => key > end,
+            Bound::Excluded(end) => key >= end,
+            Bound::Unbounded => false,
+        };
+        let mut entries = Vec::new();
+        merge(sources, past_end, |key, value| {
+            if let (true, Some(value)) = (bounds.contains(&key), value) {
+                entries.push((key, value));
+            }
+            Ok(())
+        })?;
+        Ok(entries)
+    }
+
+    fn maybe_flush(&mut self) -> Result<(), LsmError> {
+        if self.memtable.len() < self.memtable_limit {
+            return Ok(());
+        }
+        self.flush()?;
+        match self.compact_at {
+            Some(runs) if self.run_count() >= runs => self.spawn_compaction(),
+            _ => Ok(()),
+        }
+    }
+
+    // Writes the memtable out as the newest run and starts a new one.
+    pub fn flush(&mut self) -> Result<(), LsmError> {
+        if self.memtable.is_empty() {
+            return Ok(());
+        }
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let mut writer = RunWriter::create(run_path(&self.shared.dir, seq), false)?;
+        for (key, value) in self.memtable.map() {
+            writer.push(key.clone(), value)?;
+        }
+        let run = Arc::new(writer.finish(seq)?);
+        self.shared.runs.write().unwrap().insert(0, run);
+        // A crash before the memtable is cleared, or a failure while clearing
+        // it, leaves entries in it that the run already holds, which changes
+        // nothing when they are read or flushed again.
+        self.memtable.clear()?;
+        Ok(())
+    }
+
+    // Starts merging the runs on another thread, unless a compaction is
+    // already under way.
+    pub fn spawn_compaction(&mut self) -> Result<(), LsmError> {
+        if self.compaction.as_ref().is_some_and(|compaction| !compaction.is_finished()) {
+            return Ok(());
+        }
+        self.wait_for_compaction()?;
+        let shared = self.shared.clone();
+        self.compaction = Some(thread::spawn(move || compact_runs::<K, V>(&shared)));
+        Ok(())
+    }
+
+    // Waits for the background compaction, if any, and returns its result.
+    pub fn wait_for_compaction(&mut self) -> Result<(), LsmError> {
+        match self.compaction.take() {
+            Some(compaction) => compaction
+                .join()
+                .unwrap_or_else(|_| Err(io::Error::other("compaction thread panicked").into())),
+            None => Ok(()),
+        }
+    }
+
+    // Merges all runs into one on the current thread.
+    pub fn compact(&mut self) -> Result<(), LsmError> {
+        self.wait_for_compaction()?;
+        compact_runs::<K, V>(&self.shared)
+    }
+}
+
+impl<K, V> Drop for LsmStore<K, V>
+    where
+        K: Ord + Clone + Codec + Send + Sync + 'static,
+        V: Clone + Codec + Send + Sync + 'static,
+{
+    fn drop(&mut self) {
+        let _ = self.wait_for_compaction();
+    }
+}
@@ -0,0 +1,337 @@
+#![forbid(unsafe_code)]
+
+use crate::augment::Augment;
+use crate::node::Link;
+use crate::tree::{quantile_index, AVLTreeMap};
+use std::borrow::Borrow;
+use std::cmp::Ordering::{Equal, Greater, Less};
+use std::collections::VecDeque;
+use std::iter::repeat_n;
+
+// How many elements one node stands for.
+trait Weighted {
+    fn weight(&self) -> usize;
+}
+
+impl Weighted for usize {
+    fn weight(&self) -> usize {
+        *self
+    }
+}
+
+impl<V> Weighted for VecDeque<V> {
+    fn weight(&self) -> usize {
+        self.len()
+    }
+}
+
+struct Multiplicity;
+
+impl<K, W: Weighted> Augment<K, W> for Multiplicity {
+    type Summary = usize;
+
+    fn identity() -> usize {
+        0
+    }
+
+    fn lift(_: &K, value: &W) -> usize {
+        value.weight()
+    }
+
+    fn combine(left: &usize, right: &usize) -> usize {
+        left + right
+    }
+}
+
+fn subtree_weight<K: Ord, W: Weighted>(node: &Link<K, W, Multiplicity>) -> usize {
+    node.as_ref().map_or(0, |node| *node.summary())
+}
+
+// The node holding the `index`-th element counting repeats, and the position
+// of that element among the node's own ones.
+fn select<K: Ord, W: Weighted>(
+    mut node: &Link<K, W, Multiplicity>,
+    mut index: usize,
+) -> Option<(&K, &W, usize)> {
+    while let Some(node_ref) = node {
+        let left = subtree_weight(node_ref.get_left());
+        let (key, value) = node_ref.get_key_value();
+        if index < left {
+            node = node_ref.get_left();
+        } else if index < left + value.weight() {
+            return Some((key, value, index - left));
+        } else {
+            index -= left + value.weight();
+            node = node_ref.get_right();
+        }
+    }
+    None
+}
+
+// Number of elements, counting repeats, with a key below `key`.
+fn rank<K, W, Q>(mut node: &Link<K, W, Multiplicity>, key: &Q) -> usize
+    where
+        K: Ord + Borrow<Q>,
+        W: Weighted,
+        Q: Ord + ?Sized,
+{
+    let mut below = 0;
+    while let Some(node_ref) = node {
+        match key.cmp(node_ref.get_key_borrowed()) {
+            Less => node = node_ref.get_left(),
+            Greater => {
+                below += subtree_weight(node_ref.get_left()) + node_ref.get_key_value().1.weight();
+                node = node_ref.get_right();
+            }
+            Equal => return below + subtree_weight(node_ref.get_left()),
+        }
+    }
+    below
+}
+
+pub struct AVLMultiSet<T: Ord> {
+    map: AVLTreeMap<T, usize, Multiplicity>,
+}
+
+impl<T: Ord> Default for AVLMultiSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord + Clone> Clone for AVLMultiSet<T> {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<T: Ord> AVLMultiSet<T> {
+    pub fn new() -> Self {
+        Self {
+            map: AVLTreeMap::with_augment(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.summary()
+    }
+
+    pub fn distinct_len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn insert(&mut self, value: T) {
+        if self.map.update(&value, |count| *count += 1).is_none() {
+            self.map.insert(value, 1);
+        }
+    }
+
+    pub fn count<Q>(&self, value: &Q) -> usize
+        where
+            T: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.map.get(value).copied().unwrap_or(0)
+    }
+
+    pub fn contains<Q>(&self, value: &Q) -> bool
+        where
+            T: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.map.contains_key(value)
+    }
+
+    pub fn remove_one<Q>(&mut self, value: &Q) -> bool
+        where
+            T: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        match self.map.update(value, |count| {
+            *count -= 1;
+            *count
+        }) {
+            Some(0) => {
+                self.map.remove(value);
+                true
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    pub fn remove_all<Q>(&mut self, value: &Q) -> usize
+        where
+            T: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.map.remove(value).unwrap_or(0)
+    }
+
+    pub fn nth(&self, index: usize) -> Option<&T> {
+        select(self.map.root(), index).map(|(value, _, _)| value)
+    }
+
+    pub fn rank<Q>(&self, value: &Q) -> usize
+        where
+            T: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        rank(self.map.root(), value)
+    }
+
+    // The element `q` of the way through, counting repeats and rounding down.
+    pub fn quantile(&self, q: f64) -> Option<&T> {
+        self.nth(quantile_index(q, self.len())?)
+    }
+
+    pub fn median(&self) -> Option<&T> {
+        self.quantile(0.5)
+    }
+
+    // Share of the elements that are at most `value`.
+    pub fn percentile_rank<Q>(&self, value: &Q) -> Option<f64>
+        where
+            T: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        if self.is_empty() {
+            return None;
+        }
+        Some((self.rank(value) + self.count(value)) as f64 / self.len() as f64)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.map.iter().flat_map(|(value, count)| repeat_n(value, *count))
+    }
+
+    pub fn iter_counts(&self) -> impl Iterator<Item = (&T, usize)> {
+        self.map.iter().map(|(value, count)| (value, *count))
+    }
+}
+
+// Values with equal keys share a node and keep their insertion order.
+pub struct AVLMultiMap<K: Ord, V> {
+    map: AVLTreeMap<K, VecDeque<V>, Multiplicity>,
+}
+
+impl<K: Ord, V> Default for AVLMultiMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Clone for AVLMultiMap<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<K: Ord, V> AVLMultiMap<K, V> {
+    pub fn new() -> Self {
+        Self {
+            map: AVLTreeMap::with_augment(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.summary()
+    }
+
+    pub fn keys_len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        let mut value = Some(value);
+        self.map.update(&key, |values| values.push_back(value.take().unwrap()));
+        if let Some(value) = value {
+            self.map.insert(key, VecDeque::from([value]));
+        }
+    }
+
+    pub fn get_all<Q>(&self, key: &Q) -> impl Iterator<Item = &V>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.map.get(key).into_iter().flatten()
+    }
+
+    pub fn first<Q>(&self, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.map.get(key).and_then(|values| values.front())
+    }
+
+    pub fn count<Q>(&self, key: &Q) -> usize
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.map.get(key).map_or(0, |values| values.len())
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.map.contains_key(key)
+    }
+
+    // Removes the earliest inserted value of `key`.
+    pub fn remove_one<Q>(&mut self, key: &Q) -> Option<V>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        let (value, left) = self
+            .map
+            .update(key, |values| (values.pop_front(), values.len()))?;
+        if left == 0 {
+            self.map.remove(key);
+        }
+        value
+    }
+
+    pub fn remove_all<Q>(&mut self, key: &Q) -> Vec<V>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.map.remove(key).map_or_else(Vec::new, Vec::from)
+    }
+
+    pub fn nth(&self, index: usize) -> Option<(&K, &V)> {
+        select(self.map.root(), index).map(|(key, values, offset)| (key, &values[offset]))
+    }
+
+    pub fn rank<Q>(&self, key: &Q) -> usize
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        rank(self.map.root(), key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.map
+            .iter()
+            .flat_map(|(key, values)| values.iter().map(move |value| (key, value)))
+    }
+}
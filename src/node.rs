@@ -1,18 +1,44 @@
 #![forbid(unsafe_code)]
 
+use crate::augment::Augment;
 use std::borrow::Borrow;
 use std::mem::replace;
+use std::sync::Arc;
 
-pub struct Node<K, V> {
-    left: Option<Box<Node<K, V>>>,
-    right: Option<Box<Node<K, V>>>,
+pub type Link<K, V, A = ()> = Option<Arc<Node<K, V, A>>>;
+
+// Copies a node that is still shared with another map. It is only known once
+// the map has been cloned, which is also the only way nodes become shared.
+pub type CloneFn<K, V, A> = fn(&Node<K, V, A>) -> Node<K, V, A>;
+pub type Unshare<K, V, A> = Option<CloneFn<K, V, A>>;
+pub type PartsMut<'a, K, V, A> = (&'a mut Link<K, V, A>, &'a K, &'a mut V, &'a mut Link<K, V, A>);
+
+pub struct Node<K, V, A: Augment<K, V> = ()> {
+    left: Link<K, V, A>,
+    right: Link<K, V, A>,
     balance_factor: Factor,
     key: K,
     value: Option<V>,
+    summary: A::Summary,
     pub left_count: usize,
     pub right_count: usize,
 }
 
+impl<K: Clone, V: Clone, A: Augment<K, V>> Clone for Node<K, V, A> {
+    fn clone(&self) -> Self {
+        Self {
+            left: self.left.clone(),
+            right: self.right.clone(),
+            balance_factor: self.balance_factor,
+            key: self.key.clone(),
+            value: self.value.clone(),
+            summary: self.summary.clone(),
+            left_count: self.left_count,
+            right_count: self.right_count,
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq)]
 pub enum Factor {
     LeftHeavy,
@@ -30,15 +56,31 @@ impl Factor {
     }
 }
 
-impl<K: Ord, V> From<Node<K, V>> for Option<Box<Node<K, V>>> {
-    fn from(node: Node<K, V>) -> Self {
-        Some(Box::new(node))
+impl<K: Ord, V, A: Augment<K, V>> From<Node<K, V, A>> for Link<K, V, A> {
+    fn from(node: Node<K, V, A>) -> Self {
+        Some(Arc::new(node))
     }
 }
 
-impl<K: Ord, V> Node<K, V> {
+pub fn make_mut<K, V, A: Augment<K, V>>(node: &mut Arc<Node<K, V, A>>, unshare: Unshare<K, V, A>) -> &mut Node<K, V, A> {
+    if Arc::strong_count(node) != 1 {
+        let clone = unshare.expect("nodes are shared only between cloned maps");
+        *node = Arc::new(clone(node));
+    }
+    Arc::get_mut(node).unwrap()
+}
+
+pub fn into_inner<K, V, A: Augment<K, V>>(node: Arc<Node<K, V, A>>, unshare: Unshare<K, V, A>) -> Node<K, V, A> {
+    Arc::try_unwrap(node).unwrap_or_else(|node| {
+        let clone = unshare.expect("nodes are shared only between cloned maps");
+        clone(&node)
+    })
+}
+
+impl<K: Ord, V, A: Augment<K, V>> Node<K, V, A> {
     pub fn new(key: K, value: V) -> Self {
         Self {
+            summary: A::summarize(None, &key, &value, None),
             left: None,
             right: None,
             balance_factor: Factor::Balanced,
@@ -53,14 +95,22 @@ impl<K: Ord, V> Node<K, V> {
         &self.balance_factor
     }
 
-    pub fn get_left(&self) -> &Option<Box<Node<K, V>>> {
+    pub fn get_left(&self) -> &Link<K, V, A> {
         &self.left
     }
 
-    pub fn get_right(&self) -> &Option<Box<Node<K, V>>> {
+    pub fn get_right(&self) -> &Link<K, V, A> {
         &self.right
     }
 
+    pub fn get_left_mut(&mut self) -> &mut Link<K, V, A> {
+        &mut self.left
+    }
+
+    pub fn get_right_mut(&mut self) -> &mut Link<K, V, A> {
+        &mut self.right
+    }
+
     pub fn get_key(&self) -> &K {
         &self.key
     }
@@ -80,27 +130,41 @@ impl<K: Ord, V> Node<K, V> {
         self.value.as_ref()
     }
 
+    pub fn get_value_mut(&mut self) -> Option<&mut V> {
+        self.value.as_mut()
+    }
+
     pub fn get_key_value(&self) -> (&K, &V) {
-        (&self.key, &self.value.as_ref().unwrap())
+        (&self.key, self.value.as_ref().unwrap())
     }
 
-    pub fn set_right(&mut self, node: Option<Box<Node<K, V>>>) {
+    pub fn set_right(&mut self, node: Link<K, V, A>) {
         self.right = node;
     }
 
-    pub fn set_left(&mut self, node: Option<Box<Node<K, V>>>) {
+    pub fn set_left(&mut self, node: Link<K, V, A>) {
         self.left = node;
     }
 
+    pub fn update_value<F: FnOnce(&K, V) -> V>(&mut self, f: F) {
+        let value = self.value.take().unwrap();
+        self.value = Some(f(&self.key, value));
+    }
+
+    // The children, key and value at once, borrowed apart.
+    pub fn parts_mut(&mut self) -> PartsMut<'_, K, V, A> {
+        (&mut self.left, &self.key, self.value.as_mut().unwrap(), &mut self.right)
+    }
+
     pub fn replace_value(&mut self, value: V) -> V {
         self.value.replace(value).unwrap()
     }
 
-    pub fn take_left(&mut self) -> Option<Box<Node<K, V>>> {
+    pub fn take_left(&mut self) -> Link<K, V, A> {
         self.left.take()
     }
 
-    pub fn take_right(&mut self) -> Option<Box<Node<K, V>>> {
+    pub fn take_right(&mut self) -> Link<K, V, A> {
         self.right.take()
     }
 
@@ -125,4 +189,12 @@ impl<K: Ord, V> Node<K, V> {
     pub fn right_count(&self) -> usize {
         self.right_count
     }
+
+    pub fn summary(&self) -> &A::Summary {
+        &self.summary
+    }
+
+    pub fn set_summary(&mut self, summary: A::Summary) {
+        self.summary = summary;
+    }
 }
@@ -0,0 +1,69 @@
+#![forbid(unsafe_code)]
+
+use crate::augment::Augment;
+use crate::iter::Iter;
+use crate::tree::AVLTreeMap;
+use std::borrow::Borrow;
+use std::ops::Bound;
+
+// One page of entries in key order. `offset` is the rank of the first item
+// and `total` the size of the whole map. The cursors are set only when there
+// are entries past that side of the page: pass `next_cursor` to `page_after`
+// and `prev_cursor` to `page_before` to move on.
+#[derive(Debug, PartialEq)]
+pub struct Page<'a, K, V> {
+    pub items: Vec<(&'a K, &'a V)>,
+    pub next_cursor: Option<&'a K>,
+    pub prev_cursor: Option<&'a K>,
+    pub total: usize,
+    pub offset: usize,
+}
+
+impl<K: Ord, V, A: Augment<K, V>> AVLTreeMap<K, V, A> {
+    // Up to `limit` entries with keys above `cursor`.
+    pub fn page_after<Q>(&self, cursor: &Q, limit: usize) -> Page<'_, K, V>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        let start = self.rank_range((Bound::Excluded(cursor), Bound::Unbounded)).start;
+        self.page(start, limit)
+    }
+
+    // Up to `limit` entries with keys below `cursor`, the closest ones.
+    pub fn page_before<Q>(&self, cursor: &Q, limit: usize) -> Page<'_, K, V>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        let end = self.rank_range((Bound::Unbounded, Bound::Excluded(cursor))).end;
+        let start = end.saturating_sub(limit);
+        self.page(start, end - start)
+    }
+
+    // Up to `limit` entries from rank `offset` on, found in O(log n).
+    pub fn page_at_offset(&self, offset: usize, limit: usize) -> Page<'_, K, V> {
+        self.page(offset.min(self.len()), limit)
+    }
+
+    fn page(&self, offset: usize, limit: usize) -> Page<'_, K, V> {
+        let total = self.len();
+        let len = limit.min(total - offset);
+        let items: Vec<_> = Iter::new(self.root(), offset, len).collect();
+        let next_cursor = match items.last() {
+            Some((key, _)) if offset + len < total => Some(*key),
+            _ => None,
+        };
+        let prev_cursor = match items.first() {
+            Some((key, _)) if offset > 0 => Some(*key),
+            _ => None,
+        };
+        Page {
+            items,
+            next_cursor,
+            prev_cursor,
+            total,
+            offset,
+        }
+    }
+}
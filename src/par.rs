@@ -0,0 +1,331 @@
+#![forbid(unsafe_code)]
+
+use crate::augment::Augment;
+use crate::node::{make_mut, Link, Unshare};
+use crate::partition::RankView;
+use crate::salvage::Salvage;
+use crate::tree::Direction::{Left, Right};
+use crate::tree::{
+    assemble, build, child_height, concat, dedup_last, join, retain, union, union_step, AVLTreeMap, Measured,
+};
+use rayon::iter::plumbing::{bridge_unindexed, Folder, UnindexedConsumer, UnindexedProducer};
+use rayon::prelude::*;
+use std::mem::take;
+use std::ops::ControlFlow::{Break, Continue};
+
+// Subtrees smaller than this are handled on the current thread.
+const SEQUENTIAL_BELOW: usize = 1 << 12;
+
+fn subtree_len<K, V, A: Augment<K, V>>(node: &Link<K, V, A>) -> usize {
+    node.as_ref().map_or(0, |node| node.left_count + node.right_count + 1)
+}
+
+pub struct ParIter<'a, K, V, A: Augment<K, V> = ()> {
+    view: RankView<'a, K, V, A>,
+}
+
+impl<'a, K, V, A> ParallelIterator for ParIter<'a, K, V, A>
+    where
+        K: Ord + Send + Sync,
+        V: Send + Sync,
+        A: Augment<K, V>,
+        A::Summary: Send + Sync,
+{
+    type Item = (&'a K, &'a V);
+
+    fn drive_unindexed<C: UnindexedConsumer<Self::Item>>(self, consumer: C) -> C::Result {
+        bridge_unindexed(self.view, consumer)
+    }
+}
+
+// Halving a rank range is O(1). Each half then seeks its start in O(log n).
+impl<'a, K, V, A> UnindexedProducer for RankView<'a, K, V, A>
+    where
+        K: Ord + Send + Sync,
+        V: Send + Sync,
+        A: Augment<K, V>,
+        A::Summary: Send + Sync,
+{
+    type Item = (&'a K, &'a V);
+
+    fn split(self) -> (Self, Option<Self>) {
+        match RankView::split(self) {
+            Some((left, right)) => (left, Some(right)),
+            None => (self, None),
+        }
+    }
+
+    fn fold_with<F: Folder<Self::Item>>(self, folder: F) -> F {
+        folder.consume_iter(self.iter())
+    }
+}
+
+impl<'a, K, V, A> IntoParallelIterator for &'a AVLTreeMap<K, V, A>
+    where
+        K: Ord + Send + Sync,
+        V: Send + Sync,
+        A: Augment<K, V>,
+        A::Summary: Send + Sync,
+{
+    type Iter = ParIter<'a, K, V, A>;
+    type Item = (&'a K, &'a V);
+
+    fn into_par_iter(self) -> Self::Iter {
+        ParIter {
+            view: self.rank_view(),
+        }
+    }
+}
+
+enum Piece<'a, K, V> {
+    Tree(&'a mut Link<K, V>),
+    Entry(&'a K, &'a mut V),
+}
+
+// Pieces of the tree in key order. A lone subtree splits at its root, which
+// unshares the root first, so each side owns what it hands out.
+pub struct ParIterMut<'a, K, V> {
+    pieces: Vec<Piece<'a, K, V>>,
+    unshare: Unshare<K, V, ()>,
+}
+
+impl<'a, K, V> ParallelIterator for ParIterMut<'a, K, V>
+    where
+        K: Ord + Send + Sync,
+        V: Send + Sync,
+{
+    type Item = (&'a K, &'a mut V);
+
+    fn drive_unindexed<C: UnindexedConsumer<Self::Item>>(self, consumer: C) -> C::Result {
+        bridge_unindexed(self, consumer)
+    }
+}
+
+impl<'a, K, V> UnindexedProducer for ParIterMut<'a, K, V>
+    where
+        K: Ord + Send + Sync,
+        V: Send + Sync,
+{
+    type Item = (&'a K, &'a mut V);
+
+    fn split(mut self) -> (Self, Option<Self>) {
+        if let [Piece::Tree(Some(_))] = self.pieces.as_slice() {
+            let Some(Piece::Tree(Some(node))) = self.pieces.pop() else {
+                unreachable!()
+            };
+            let (left, key, value, right) = make_mut(node, self.unshare).parts_mut();
+            self.pieces = vec![Piece::Tree(left), Piece::Entry(key, value), Piece::Tree(right)];
+        }
+        if self.pieces.len() < 2 {
+            return (self, None);
+        }
+        let right = Self {
+            pieces: self.pieces.split_off(self.pieces.len() / 2),
+            unshare: self.unshare,
+        };
+        (self, Some(right))
+    }
+
+    fn fold_with<F: Folder<Self::Item>>(self, mut folder: F) -> F {
+        for piece in self.pieces {
+            folder = match piece {
+                Piece::Tree(node) => fold_mut(node, folder, self.unshare),
+                Piece::Entry(key, value) => folder.consume((key, value)),
+            };
+            if folder.full() {
+                break;
+            }
+        }
+        folder
+    }
+}
+
+fn fold_mut<'a, K: Ord, V, F>(node: &'a mut Link<K, V>, mut folder: F, unshare: Unshare<K, V, ()>) -> F
+    where
+        F: Folder<(&'a K, &'a mut V)>,
+{
+    let Some(node) = node else {
+        return folder;
+    };
+    let (left, key, value, right) = make_mut(node, unshare).parts_mut();
+    folder = fold_mut(left, folder, unshare);
+    if folder.full() {
+        return folder;
+    }
+    folder = folder.consume((key, value));
+    fold_mut(right, folder, unshare)
+}
+
+// Only without an augment, as changing values would leave summaries stale.
+impl<'a, K, V> IntoParallelIterator for &'a mut AVLTreeMap<K, V>
+    where
+        K: Ord + Send + Sync,
+        V: Send + Sync,
+{
+    type Iter = ParIterMut<'a, K, V>;
+    type Item = (&'a K, &'a mut V);
+
+    fn into_par_iter(self) -> Self::Iter {
+        let unshare = self.unshare();
+        ParIterMut {
+            pieces: vec![Piece::Tree(self.root_mut())],
+            unshare,
+        }
+    }
+}
+
+// Builds the tree of a sorted run of entries, the two halves in parallel.
+fn par_build<K, V, A>(entries: &mut [Option<(K, V)>]) -> Link<K, V, A>
+    where
+        K: Ord + Send + Sync,
+        V: Send + Sync,
+        A: Augment<K, V>,
+        A::Summary: Send + Sync,
+{
+    if entries.len() < SEQUENTIAL_BELOW {
+        let len = entries.len();
+        return build(&mut entries.iter_mut().map(|entry| entry.take().unwrap()), len);
+    }
+    let (left, rest) = entries.split_at_mut(entries.len() / 2);
+    let (middle, right) = rest.split_first_mut().unwrap();
+    let (left, right) = rayon::join(|| par_build(left), || par_build(right));
+    let (key, value) = middle.take().unwrap();
+    assemble(left, key, value, right).into()
+}
+
+// Sorts the entries in parallel and builds the two halves of every large
+// subtree on separate threads. Of equal keys the last wins.
+impl<K, V, A> FromParallelIterator<(K, V)> for AVLTreeMap<K, V, A>
+    where
+        K: Ord + Send + Sync,
+        V: Send + Sync,
+        A: Augment<K, V>,
+        A::Summary: Send + Sync,
+{
+    fn from_par_iter<I: IntoParallelIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut entries: Vec<_> = iter.into_par_iter().collect();
+        entries.par_sort_by(|a, b| a.0.cmp(&b.0));
+        dedup_last(&mut entries);
+        let mut entries: Vec<_> = entries.into_iter().map(Some).collect();
+        let mut map = Self::with_augment();
+        map.set_tree(par_build(&mut entries));
+        map
+    }
+}
+
+fn par_retain<K, V, A, F>(
+    tree: Measured<K, V, A>,
+    f: &F,
+    unshare: Unshare<K, V, A>,
+    salvage: &Salvage<K, V, A>,
+) -> Measured<K, V, A>
+    where
+        K: Ord + Send + Sync,
+        V: Send + Sync,
+        A: Augment<K, V>,
+        A::Summary: Send + Sync,
+        F: Fn(&K, &mut V) -> bool + Sync,
+{
+    let (Some(mut node), height) = tree else {
+        return (None, 0);
+    };
+    if node.left_count + node.right_count + 1 < SEQUENTIAL_BELOW {
+        return retain((Some(node), height), &mut |key, value| f(key, value), unshare, salvage);
+    }
+    let current = make_mut(&mut node, unshare);
+    let left = (current.take_left(), child_height(current, height, Left));
+    let right = (current.take_right(), child_height(current, height, Right));
+    let mut held = salvage.hold();
+    held.node = Some(node);
+    held.own = [left, right];
+    let (_, key, value, _) = make_mut(held.node.as_mut().unwrap(), unshare).parts_mut();
+    if !f(key, value) {
+        held.node = None;
+    }
+    let [left, right] = take(&mut held.own);
+    // Each side's result is held, so it is salvaged if the other side panics.
+    let (mut left, mut right) = rayon::join(
+        || salvage.hold_own(par_retain(left, f, unshare, salvage)),
+        || salvage.hold_own(par_retain(right, f, unshare, salvage)),
+    );
+    let (left, right) = (take(&mut left.own[0]), take(&mut right.own[0]));
+    match held.node.take() {
+        Some(node) => {
+            let (node, height) = join(left, node, right, unshare);
+            (node.into(), height)
+        }
+        None => concat(left, right, unshare),
+    }
+}
+
+fn par_union<K, V, A, F>(
+    left: Measured<K, V, A>,
+    right: Measured<K, V, A>,
+    f: &F,
+    unshare: Unshare<K, V, A>,
+    salvage: &Salvage<K, V, A>,
+) -> Measured<K, V, A>
+    where
+        K: Ord + Send + Sync,
+        V: Send + Sync,
+        A: Augment<K, V>,
+        A::Summary: Send + Sync,
+        F: Fn(&K, V, V) -> V + Sync,
+{
+    let mut combine = |key: &K, own, other| f(key, own, other);
+    if subtree_len(&left.0) + subtree_len(&right.0) < SEQUENTIAL_BELOW {
+        return union(left, right, &mut combine, unshare, salvage);
+    }
+    match union_step(left, right, &mut combine, unshare, salvage) {
+        Continue((node, (left, lower), (right, upper))) => {
+            let mut held = salvage.hold();
+            held.node = Some(node);
+            let (mut left, mut right) = rayon::join(
+                || salvage.hold_own(par_union(left, lower, f, unshare, salvage)),
+                || salvage.hold_own(par_union(right, upper, f, unshare, salvage)),
+            );
+            let (left, right) = (take(&mut left.own[0]), take(&mut right.own[0]));
+            let (node, height) = join(left, held.node.take().unwrap(), right, unshare);
+            (node.into(), height)
+        }
+        Break(done) => done,
+    }
+}
+
+impl<K, V, A> AVLTreeMap<K, V, A>
+    where
+        K: Ord + Send + Sync,
+        V: Send + Sync,
+        A: Augment<K, V>,
+        A::Summary: Send + Sync,
+{
+    // `retain` with the subtrees filtered on separate threads. `f` sees the
+    // entries in no particular order. If it panics, the map keeps every
+    // entry it has not removed so far.
+    pub fn par_retain<F>(&mut self, f: F)
+        where
+            F: Fn(&K, &mut V) -> bool + Sync,
+    {
+        let unshare = self.unshare();
+        let tree = self.take_tree();
+        let salvage = Salvage::new(unshare);
+        let restore = salvage.restore(self);
+        let (root, _) = par_retain(tree, &f, unshare, &salvage);
+        restore.map.set_tree(root);
+    }
+
+    // `union_with` with the merges on either side of each split running on
+    // separate threads, and the same outcome if `f` panics.
+    pub fn par_union_with<F>(&mut self, mut other: Self, f: F)
+        where
+            F: Fn(&K, V, V) -> V + Sync,
+    {
+        self.inherit_clone_node(&other);
+        let unshare = self.unshare();
+        let (left, right) = (self.take_tree(), other.take_tree());
+        let salvage = Salvage::new(unshare);
+        let restore = salvage.restore(self);
+        let (root, _) = par_union(left, right, &f, unshare, &salvage);
+        restore.map.set_tree(root);
+    }
+}
@@ -0,0 +1,101 @@
+#![forbid(unsafe_code)]
+
+use crate::augment::Augment;
+use crate::iter::Iter;
+use crate::node::Link;
+use crate::tree::AVLTreeMap;
+
+// The entries at ranks `[offset, offset + len)` of a borrowed map. Views are
+// cheap to copy and to split, and can be sent to other threads whenever the
+// map could be shared with them.
+pub struct RankView<'a, K, V, A: Augment<K, V> = ()> {
+    root: &'a Link<K, V, A>,
+    offset: usize,
+    len: usize,
+}
+
+impl<'a, K, V, A: Augment<K, V>> Clone for RankView<'a, K, V, A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, K, V, A: Augment<K, V>> Copy for RankView<'a, K, V, A> {}
+
+impl<'a, K: Ord, V, A: Augment<K, V>> RankView<'a, K, V, A> {
+    // Rank of the first entry within the whole map.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // The first and last keys, bounding the view.
+    pub fn key_bounds(&self) -> Option<(&'a K, &'a K)> {
+        let last = self.len.checked_sub(1)?;
+        let (first, _) = Iter::new(self.root, self.offset, 1).next()?;
+        let (last, _) = Iter::new(self.root, self.offset + last, 1).next()?;
+        Some((first, last))
+    }
+
+    pub fn iter(&self) -> Iter<'a, K, V, A> {
+        Iter::new(self.root, self.offset, self.len)
+    }
+
+    // The first `mid` entries and the rest.
+    pub fn split_at(self, mid: usize) -> (Self, Self) {
+        let len = self.len;
+        assert!(mid <= len, "mid (is {mid}) should be <= len (is {len})");
+        let right = Self {
+            offset: self.offset + mid,
+            len: len - mid,
+            ..self
+        };
+        (Self { len: mid, ..self }, right)
+    }
+
+    // Two halves, for as long as there is more than one entry to hand out.
+    pub fn split(self) -> Option<(Self, Self)> {
+        (self.len > 1).then(|| self.split_at(self.len / 2))
+    }
+}
+
+impl<'a, K: Ord, V, A: Augment<K, V>> IntoIterator for RankView<'a, K, V, A> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Ord, V, A: Augment<K, V>> AVLTreeMap<K, V, A> {
+    pub fn rank_view(&self) -> RankView<'_, K, V, A> {
+        RankView {
+            root: self.root(),
+            offset: 0,
+            len: self.len(),
+        }
+    }
+
+    // `k` views covering the map in order, whose lengths differ by at most
+    // one. Only the rank offsets are computed, so this takes O(k).
+    pub fn chunks_by_rank(&self, k: usize) -> Vec<RankView<'_, K, V, A>> {
+        assert!(k > 0, "chunk count should be positive");
+        let (base, extra) = (self.len() / k, self.len() % k);
+        let mut rest = self.rank_view();
+        (0..k)
+            .map(|index| {
+                let (chunk, tail) = rest.split_at(base + (index < extra) as usize);
+                rest = tail;
+                chunk
+            })
+            .collect()
+    }
+}
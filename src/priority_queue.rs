@@ -0,0 +1,115 @@
+#![forbid(unsafe_code)]
+
+use crate::tree::AVLTreeMap;
+use std::collections::HashMap;
+use std::mem::replace;
+
+// Names one pushed item for as long as it is queued. Nodes move around on
+// every rotation, so a handle is the item's push number rather than a node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Handle(u64);
+
+// Items ordered by priority, then by push order, so equal priorities leave
+// the queue first in, first out from either end.
+pub struct AVLPriorityQueue<P: Ord + Clone, T> {
+    priorities: HashMap<u64, P>,
+    items: AVLTreeMap<(P, u64), T>,
+    next_seq: u64,
+}
+
+impl<P: Ord + Clone, T> Default for AVLPriorityQueue<P, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Ord + Clone, T: Clone> Clone for AVLPriorityQueue<P, T> {
+    fn clone(&self) -> Self {
+        Self {
+            priorities: self.priorities.clone(),
+            items: self.items.clone(),
+            next_seq: self.next_seq,
+        }
+    }
+}
+
+impl<P: Ord + Clone, T> AVLPriorityQueue<P, T> {
+    pub fn new() -> Self {
+        Self {
+            priorities: HashMap::new(),
+            items: AVLTreeMap::new(),
+            next_seq: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn push(&mut self, priority: P, value: T) -> Handle {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.priorities.insert(seq, priority.clone());
+        self.items.insert((priority, seq), value);
+        Handle(seq)
+    }
+
+    pub fn peek_min(&self) -> Option<(&P, &T)> {
+        self.items.nth_key_value(0).map(|((priority, _), value)| (priority, value))
+    }
+
+    pub fn peek_max(&self) -> Option<(&P, &T)> {
+        let rank = self.max_rank()?;
+        self.items.nth_key_value(rank).map(|((priority, _), value)| (priority, value))
+    }
+
+    pub fn pop_min(&mut self) -> Option<(P, T)> {
+        let (_, seq) = *self.items.nth_key_value(0)?.0;
+        self.remove(Handle(seq))
+    }
+
+    // The earliest pushed of the items with the highest priority.
+    pub fn pop_max(&mut self) -> Option<(P, T)> {
+        let rank = self.max_rank()?;
+        let (_, seq) = *self.items.nth_key_value(rank)?.0;
+        self.remove(Handle(seq))
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<(&P, &T)> {
+        let priority = self.priorities.get(&handle.0)?;
+        self.items
+            .get_key_value(&(priority.clone(), handle.0))
+            .map(|((priority, _), value)| (priority, value))
+    }
+
+    pub fn contains(&self, handle: Handle) -> bool {
+        self.priorities.contains_key(&handle.0)
+    }
+
+    // Moves a queued item to `priority`, keeping its place among equals.
+    // Returns the old priority, or `None` if the item has left the queue.
+    pub fn change_priority(&mut self, handle: Handle, priority: P) -> Option<P> {
+        let current = self.priorities.get_mut(&handle.0)?;
+        let old = replace(current, priority.clone());
+        let value = self.items.remove(&(old.clone(), handle.0)).unwrap();
+        self.items.insert((priority, handle.0), value);
+        Some(old)
+    }
+
+    pub fn remove(&mut self, handle: Handle) -> Option<(P, T)> {
+        let priority = self.priorities.remove(&handle.0)?;
+        self.items
+            .remove_entry(&(priority, handle.0))
+            .map(|((priority, _), value)| (priority, value))
+    }
+
+    // Rank of the first item with the highest priority.
+    fn max_rank(&self) -> Option<usize> {
+        let ((max, _), _) = self.items.nth_key_value(self.len().checked_sub(1)?)?;
+        Some(self.items.partition_point(|(priority, _)| priority < max))
+    }
+}
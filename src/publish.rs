@@ -0,0 +1,89 @@
+#![forbid(unsafe_code)]
+
+use crate::augment::Augment;
+use crate::tree::AVLTreeMap;
+use std::sync::{Arc, RwLock};
+
+// A map with one writer and any number of readers. The writer changes its
+// own copy freely and `publish`es it when it is ready to be seen; readers
+// take the latest published version with `snapshot` and query it for as
+// long as they like.
+//
+// Publishing clones the map, which is O(1): the version shares every node
+// with the writer's copy, and the writer's next changes copy only the paths
+// they touch, rotations included. The lock is held just to swap or clone
+// the `Arc`, so readers never wait on the writer's changes, nor it on them.
+pub struct Publisher<K: Ord, V, A: Augment<K, V> = ()> {
+    map: AVLTreeMap<K, V, A>,
+    published: Arc<RwLock<Arc<AVLTreeMap<K, V, A>>>>,
+}
+
+// A handle for readers of a `Publisher`, which can be cloned and sent to
+// other threads.
+pub struct Reader<K: Ord, V, A: Augment<K, V> = ()> {
+    published: Arc<RwLock<Arc<AVLTreeMap<K, V, A>>>>,
+}
+
+impl<K: Ord, V, A: Augment<K, V>> Clone for Reader<K, V, A> {
+    fn clone(&self) -> Self {
+        Self {
+            published: Arc::clone(&self.published),
+        }
+    }
+}
+
+impl<K: Ord + Clone, V: Clone, A: Augment<K, V>> Default for Publisher<K, V, A> {
+    fn default() -> Self {
+        Self::from_map(AVLTreeMap::with_augment())
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Publisher<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<K: Ord + Clone, V: Clone, A: Augment<K, V>> Publisher<K, V, A> {
+    // Starts with `map` both as the writer's copy and as the first version.
+    pub fn from_map(map: AVLTreeMap<K, V, A>) -> Self {
+        Self {
+            published: Arc::new(RwLock::new(Arc::new(map.clone()))),
+            map,
+        }
+    }
+
+    pub fn map(&self) -> &AVLTreeMap<K, V, A> {
+        &self.map
+    }
+
+    // The writer's copy, whose changes readers see from the next `publish`.
+    pub fn map_mut(&mut self) -> &mut AVLTreeMap<K, V, A> {
+        &mut self.map
+    }
+
+    // Makes the writer's copy, as it is now, the version readers get.
+    pub fn publish(&mut self) {
+        let version = Arc::new(self.map.clone());
+        // The previous version is dropped outside the lock.
+        let _previous = std::mem::replace(&mut *self.published.write().unwrap(), version);
+    }
+
+    pub fn reader(&self) -> Reader<K, V, A> {
+        Reader {
+            published: Arc::clone(&self.published),
+        }
+    }
+
+    pub fn snapshot(&self) -> Arc<AVLTreeMap<K, V, A>> {
+        Arc::clone(&self.published.read().unwrap())
+    }
+}
+
+impl<K: Ord, V, A: Augment<K, V>> Reader<K, V, A> {
+    // The latest published version. It stays as it is however the writer
+    // goes on.
+    pub fn snapshot(&self) -> Arc<AVLTreeMap<K, V, A>> {
+        Arc::clone(&self.published.read().unwrap())
+    }
+}
@@ -0,0 +1,264 @@
+#![forbid(unsafe_code)]
+
+use crate::augment::Augment;
+use crate::iter::Iter;
+use crate::tree::AVLTreeMap;
+use std::mem::replace;
+use std::ops::{Add, Range, Sub};
+
+// A position in the ranged domain. Lengths are measured in the same type, so
+// the covered length is the sum of `end - start` over the stored ranges.
+pub trait Point: Ord + Clone + Default + Add<Output = Self> + Sub<Output = Self> {}
+
+impl<T: Ord + Clone + Default + Add<Output = T> + Sub<Output = T>> Point for T {}
+
+struct Length;
+
+impl<T: Point, V> Augment<T, (T, V)> for Length {
+    type Summary = T;
+
+    fn identity() -> T {
+        T::default()
+    }
+
+    fn lift(start: &T, (end, _): &(T, V)) -> T {
+        end.clone() - start.clone()
+    }
+
+    fn combine(left: &T, right: &T) -> T {
+        left.clone() + right.clone()
+    }
+}
+
+// Disjoint half-open ranges keyed by their start. Touching ranges with equal
+// values are merged on insert, so the stored ranges are always maximal.
+pub struct AVLRangeMap<T: Point, V> {
+    map: AVLTreeMap<T, (T, V), Length>,
+}
+
+impl<T: Point, V> Default for AVLRangeMap<T, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Point, V: Clone> Clone for AVLRangeMap<T, V> {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<T: Point, V> AVLRangeMap<T, V> {
+    pub fn new() -> Self {
+        Self {
+            map: AVLTreeMap::with_augment(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn covered_len(&self) -> T {
+        self.map.summary()
+    }
+
+    pub fn get(&self, point: &T) -> Option<&V> {
+        self.get_range_value(point).map(|(_, _, value)| value)
+    }
+
+    pub fn get_range_value(&self, point: &T) -> Option<(&T, &T, &V)> {
+        match self.map.floor_key_value(point) {
+            Some((start, (end, value))) if end > point => Some((start, end, value)),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, point: &T) -> bool {
+        self.get_range_value(point).is_some()
+    }
+
+    pub fn iter(&self) -> RangeIter<'_, T, V> {
+        RangeIter {
+            iter: self.map.iter(),
+        }
+    }
+
+    pub fn gaps(&self, within: Range<T>) -> Gaps<'_, T, V> {
+        Gaps {
+            map: &self.map,
+            cursor: within.start,
+            end: within.end,
+        }
+    }
+
+    pub fn insert_range(&mut self, range: Range<T>, value: V)
+        where
+            V: Clone + PartialEq,
+    {
+        if range.start >= range.end {
+            return;
+        }
+        self.remove_range(range.clone());
+        let (mut start, mut end) = (range.start, range.end);
+        if let Some((prev_start, (prev_end, prev_value))) = self.map.floor_key_value(&start) {
+            if *prev_end == start && *prev_value == value {
+                start = prev_start.clone();
+                self.map.remove(&start);
+            }
+        }
+        if let Some((_, (next_end, next_value))) = self.map.get_key_value(&end) {
+            if *next_value == value {
+                let next_start = replace(&mut end, next_end.clone());
+                self.map.remove(&next_start);
+            }
+        }
+        self.map.insert(start, (end, value));
+    }
+
+    pub fn remove_range(&mut self, range: Range<T>)
+        where
+            V: Clone,
+    {
+        let Range { start, end } = range;
+        if start >= end {
+            return;
+        }
+        if let Some((prev_start, (prev_end, value))) = self.map.floor_key_value(&start) {
+            if *prev_start < start && *prev_end > start {
+                let (prev_start, prev_end, value) = (prev_start.clone(), prev_end.clone(), value.clone());
+                if prev_end > end {
+                    self.map.insert(end.clone(), (prev_end, value.clone()));
+                }
+                self.map.insert(prev_start, (start.clone(), value));
+            }
+        }
+        while let Some((next_start, _)) = self.map.ceiling_key_value(&start) {
+            if *next_start >= end {
+                break;
+            }
+            let next_start = next_start.clone();
+            let (next_end, value) = self.map.remove(&next_start).unwrap();
+            if next_end > end {
+                self.map.insert(end, (next_end, value));
+                break;
+            }
+        }
+    }
+}
+
+pub struct RangeIter<'a, T: Point, V> {
+    iter: Iter<'a, T, (T, V), Length>,
+}
+
+impl<'a, T: Point, V> Iterator for RangeIter<'a, T, V> {
+    type Item = (&'a T, &'a T, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(start, (end, value))| (start, end, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+// Uncovered parts of `[cursor, end)`, found with one floor and one ceiling
+// lookup per gap.
+pub struct Gaps<'a, T: Point, V> {
+    map: &'a AVLTreeMap<T, (T, V), Length>,
+    cursor: T,
+    end: T,
+}
+
+impl<'a, T: Point, V> Iterator for Gaps<'a, T, V> {
+    type Item = Range<T>;
+
+    fn next(&mut self) -> Option<Range<T>> {
+        loop {
+            if self.cursor >= self.end {
+                return None;
+            }
+            match self.map.floor_key_value(&self.cursor) {
+                Some((_, (covered_end, _))) if *covered_end > self.cursor => {
+                    self.cursor = covered_end.clone();
+                }
+                _ => break,
+            }
+        }
+        let gap_end = match self.map.ceiling_key_value(&self.cursor) {
+            Some((next_start, _)) if *next_start < self.end => next_start.clone(),
+            _ => self.end.clone(),
+        };
+        let gap_start = replace(&mut self.cursor, gap_end.clone());
+        Some(gap_start..gap_end)
+    }
+}
+
+pub struct AVLRangeSet<T: Point> {
+    map: AVLRangeMap<T, ()>,
+}
+
+impl<T: Point> Default for AVLRangeSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Point> Clone for AVLRangeSet<T> {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<T: Point> AVLRangeSet<T> {
+    pub fn new() -> Self {
+        Self {
+            map: AVLRangeMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn covered_len(&self) -> T {
+        self.map.covered_len()
+    }
+
+    pub fn contains(&self, point: &T) -> bool {
+        self.map.contains(point)
+    }
+
+    pub fn get_range(&self, point: &T) -> Option<Range<&T>> {
+        self.map.get_range_value(point).map(|(start, end, _)| start..end)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Range<&T>> {
+        self.map.iter().map(|(start, end, _)| start..end)
+    }
+
+    pub fn gaps(&self, within: Range<T>) -> Gaps<'_, T, ()> {
+        self.map.gaps(within)
+    }
+
+    pub fn insert_range(&mut self, range: Range<T>) {
+        self.map.insert_range(range, ())
+    }
+
+    pub fn remove_range(&mut self, range: Range<T>) {
+        self.map.remove_range(range)
+    }
+}
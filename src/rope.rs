@@ -0,0 +1,355 @@
+#![forbid(unsafe_code)]
+
+use crate::augment::Augment;
+use crate::iter::Iter;
+use crate::node::Link;
+use crate::tree::AVLTreeMap;
+use std::fmt;
+use std::iter::{from_fn, FusedIterator};
+use std::ops::{Add, Range, Sub};
+
+// Edits never make a chunk longer than this many bytes. Shorter chunks are
+// merged with a neighbour when the two fit together.
+const MAX_CHUNK: usize = 512;
+
+#[derive(Clone, Copy, Default)]
+struct TextInfo {
+    bytes: usize,
+    chars: usize,
+    lines: usize,
+}
+
+impl TextInfo {
+    fn of(text: &str) -> Self {
+        Self {
+            bytes: text.len(),
+            chars: text.chars().count(),
+            lines: text.bytes().filter(|byte| *byte == b'\n').count(),
+        }
+    }
+}
+
+impl Add for TextInfo {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            bytes: self.bytes + other.bytes,
+            chars: self.chars + other.chars,
+            lines: self.lines + other.lines,
+        }
+    }
+}
+
+impl Sub for TextInfo {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            bytes: self.bytes - other.bytes,
+            chars: self.chars - other.chars,
+            lines: self.lines - other.lines,
+        }
+    }
+}
+
+struct Metrics;
+
+impl Augment<(), String> for Metrics {
+    type Summary = TextInfo;
+
+    fn identity() -> TextInfo {
+        TextInfo::default()
+    }
+
+    fn lift(_: &(), chunk: &String) -> TextInfo {
+        TextInfo::of(chunk)
+    }
+
+    fn combine(left: &TextInfo, right: &TextInfo) -> TextInfo {
+        *left + *right
+    }
+}
+
+fn subtree_info(node: &Link<(), String, Metrics>) -> TextInfo {
+    node.as_ref().map_or_else(TextInfo::default, |node| *node.summary())
+}
+
+// The chunk holding the `index`-th unit counted by `measure`, its rank, and
+// the text before it.
+fn locate<F>(mut node: &Link<(), String, Metrics>, mut index: usize, measure: F) -> Option<(usize, &str, TextInfo)>
+    where
+        F: Fn(&TextInfo) -> usize,
+{
+    let mut rank = 0;
+    let mut before = TextInfo::default();
+    while let Some(node_ref) = node {
+        let left = subtree_info(node_ref.get_left());
+        let own = *node_ref.summary() - left - subtree_info(node_ref.get_right());
+        if index < measure(&left) {
+            node = node_ref.get_left();
+        } else if index < measure(&left) + measure(&own) {
+            let chunk = node_ref.get_key_value().1;
+            return Some((rank + node_ref.left_count, chunk, before + left));
+        } else {
+            index -= measure(&left) + measure(&own);
+            before = before + left + own;
+            rank += node_ref.left_count + 1;
+            node = node_ref.get_right();
+        }
+    }
+    None
+}
+
+fn byte_offset(chunk: &str, char_offset: usize) -> usize {
+    chunk
+        .char_indices()
+        .nth(char_offset)
+        .map_or(chunk.len(), |(offset, _)| offset)
+}
+
+// Cuts `text` into chunks of at most `MAX_CHUNK` bytes on char boundaries.
+fn pieces(mut text: &str) -> impl Iterator<Item = &str> {
+    from_fn(move || {
+        if text.is_empty() {
+            return None;
+        }
+        let mut at = text.len().min(MAX_CHUNK);
+        while !text.is_char_boundary(at) {
+            at -= 1;
+        }
+        let (piece, rest) = text.split_at(at);
+        text = rest;
+        Some(piece)
+    })
+}
+
+// Text kept as a sequence of string chunks, one per node. Every node knows
+// the bytes, chars and line breaks of its subtree, so offsets in any of the
+// three are found in O(log n).
+pub struct AVLRope {
+    map: AVLTreeMap<(), String, Metrics>,
+}
+
+impl Default for AVLRope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for AVLRope {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl From<&str> for AVLRope {
+    fn from(text: &str) -> Self {
+        let mut rope = Self::new();
+        rope.insert_str(0, text);
+        rope
+    }
+}
+
+impl fmt::Display for AVLRope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.chunks().try_for_each(|chunk| f.write_str(chunk))
+    }
+}
+
+impl AVLRope {
+    pub fn new() -> Self {
+        Self {
+            map: AVLTreeMap::with_augment(),
+        }
+    }
+
+    pub fn len_bytes(&self) -> usize {
+        self.map.summary().bytes
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.map.summary().chars
+    }
+
+    // Line breaks plus one, so an empty rope has one empty line.
+    pub fn len_lines(&self) -> usize {
+        self.map.summary().lines + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn insert_str(&mut self, char_idx: usize, text: &str) {
+        let len = self.len_chars();
+        assert!(char_idx <= len, "char index (is {char_idx}) should be <= len (is {len})");
+        if text.is_empty() {
+            return;
+        }
+        // The chunk ending at or holding `char_idx` takes short insertions.
+        let target = locate(self.map.root(), char_idx.saturating_sub(1), |info| info.chars);
+        if let Some((rank, chunk, before)) = target {
+            if chunk.len() + text.len() <= MAX_CHUNK {
+                let at = byte_offset(chunk, char_idx - before.chars);
+                self.map.update_nth(rank, |chunk| chunk.insert_str(at, text));
+                return;
+            }
+        }
+        let rank = self.split_at(char_idx);
+        let mut count = 0;
+        for piece in pieces(text) {
+            self.map.insert_nth(rank + count, (), piece.to_owned());
+            count += 1;
+        }
+        self.merge_with_next(rank + count - 1);
+        if rank > 0 {
+            self.merge_with_next(rank - 1);
+        }
+    }
+
+    pub fn remove(&mut self, char_range: Range<usize>) {
+        let Range { start, end } = char_range;
+        let len = self.len_chars();
+        assert!(start <= end, "range starts at {start} but ends at {end}");
+        assert!(end <= len, "range end (is {end}) should be <= len (is {len})");
+        if start == end {
+            return;
+        }
+        let (rank, chunk, before) = locate(self.map.root(), start, |info| info.chars).unwrap();
+        let chunk_chars = end - before.chars;
+        if chunk_chars <= chunk.chars().count() {
+            let bytes = byte_offset(chunk, start - before.chars)..byte_offset(chunk, chunk_chars);
+            if bytes.len() < chunk.len() {
+                self.map.update_nth(rank, |chunk| chunk.replace_range(bytes, ""));
+                return;
+            }
+        }
+        let first = self.split_at(start);
+        let last = self.split_at(end);
+        let mut removed = self.map.split_off_nth(first);
+        let mut rest = removed.split_off_nth(last - first);
+        self.map.append_nth(&mut rest);
+        if first > 0 {
+            self.merge_with_next(first - 1);
+        }
+    }
+
+    pub fn char_to_byte(&self, char_idx: usize) -> usize {
+        match self.locate_char(char_idx) {
+            Some((_, chunk, before)) => before.bytes + byte_offset(chunk, char_idx - before.chars),
+            None => self.len_bytes(),
+        }
+    }
+
+    pub fn byte_to_char(&self, byte_idx: usize) -> usize {
+        let len = self.len_bytes();
+        assert!(byte_idx <= len, "byte index (is {byte_idx}) should be <= len (is {len})");
+        match locate(self.map.root(), byte_idx, |info| info.bytes) {
+            Some((_, chunk, before)) => before.chars + chunk[..byte_idx - before.bytes].chars().count(),
+            None => self.len_chars(),
+        }
+    }
+
+    // The line `char_idx` is on, counting line breaks before it.
+    pub fn char_to_line(&self, char_idx: usize) -> usize {
+        match self.locate_char(char_idx) {
+            Some((_, chunk, before)) => {
+                let head = &chunk[..byte_offset(chunk, char_idx - before.chars)];
+                before.lines + TextInfo::of(head).lines
+            }
+            None => self.map.summary().lines,
+        }
+    }
+
+    // The char index the line starts at. One past the last line is the end.
+    pub fn line_to_char(&self, line_idx: usize) -> usize {
+        let len = self.len_lines();
+        assert!(line_idx <= len, "line index (is {line_idx}) should be <= len (is {len})");
+        if line_idx == 0 {
+            return 0;
+        }
+        match locate(self.map.root(), line_idx - 1, |info| info.lines) {
+            Some((_, chunk, before)) => {
+                let (offset, _) = chunk
+                    .match_indices('\n')
+                    .nth(line_idx - 1 - before.lines)
+                    .unwrap();
+                before.chars + chunk[..=offset].chars().count()
+            }
+            None => self.len_chars(),
+        }
+    }
+
+    pub fn chunks(&self) -> Chunks<'_> {
+        Chunks {
+            iter: self.map.iter(),
+        }
+    }
+
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.chunks().flat_map(str::chars)
+    }
+
+    fn locate_char(&self, char_idx: usize) -> Option<(usize, &str, TextInfo)> {
+        let len = self.len_chars();
+        assert!(char_idx <= len, "char index (is {char_idx}) should be <= len (is {len})");
+        locate(self.map.root(), char_idx, |info| info.chars)
+    }
+
+    // Makes `char_idx` fall on a chunk boundary and returns the number of
+    // chunks before it.
+    fn split_at(&mut self, char_idx: usize) -> usize {
+        let Some((rank, chunk, before)) = locate(self.map.root(), char_idx, |info| info.chars) else {
+            return self.map.len();
+        };
+        if char_idx == before.chars {
+            return rank;
+        }
+        let at = byte_offset(chunk, char_idx - before.chars);
+        let tail = self.map.update_nth(rank, |chunk| chunk.split_off(at)).unwrap();
+        self.map.insert_nth(rank + 1, (), tail);
+        rank + 1
+    }
+
+    fn merge_with_next(&mut self, rank: usize) {
+        let fits = match (self.map.nth_key_value(rank), self.map.nth_key_value(rank + 1)) {
+            (Some((_, chunk)), Some((_, next))) => chunk.len() + next.len() <= MAX_CHUNK,
+            _ => false,
+        };
+        if fits {
+            let (_, next) = self.map.remove_nth(rank + 1).unwrap();
+            self.map.update_nth(rank, |chunk| chunk.push_str(&next));
+        }
+    }
+}
+
+pub struct Chunks<'a> {
+    iter: Iter<'a, (), String, Metrics>,
+}
+
+impl<'a> Clone for Chunks<'a> {
+    fn clone(&self) -> Self {
+        Self {
+            iter: self.iter.clone(),
+        }
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        self.iter.next().map(|(_, chunk)| chunk.as_str())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for Chunks<'a> {}
+
+impl<'a> FusedIterator for Chunks<'a> {}
@@ -0,0 +1,129 @@
+#![forbid(unsafe_code)]
+
+use crate::augment::Augment;
+use crate::node::{Link, Node, Unshare};
+use crate::tree::{concat, join, union, AVLTreeMap, Measured};
+use std::mem::take;
+use std::sync::{Arc, Mutex, PoisonError};
+
+// The subtrees an algorithm such as `retain` or `union` has cut loose while
+// it runs user code. If that code panics, each step hands back what it holds
+// as it unwinds, and `Restore` builds the map again from the pieces.
+pub(crate) struct Salvage<K: Ord, V, A: Augment<K, V>> {
+    unshare: Unshare<K, V, A>,
+    // From the map being changed, including what is already merged into it.
+    own: Mutex<Vec<Measured<K, V, A>>>,
+    // From the map being merged in.
+    other: Mutex<Vec<Measured<K, V, A>>>,
+}
+
+// Pieces a step of an algorithm holds while it runs user code. Dropping it
+// with pieces still in it, which only unwinding does, hands them over to the
+// `Salvage`; the step takes them out as it goes on.
+pub(crate) struct Held<'s, K: Ord, V, A: Augment<K, V>> {
+    salvage: &'s Salvage<K, V, A>,
+    // A node cut off from its children. It is dropped if its value is
+    // gone, moved into the user code that panicked.
+    pub(crate) node: Link<K, V, A>,
+    pub(crate) own: [Measured<K, V, A>; 2],
+    pub(crate) other: [Measured<K, V, A>; 2],
+}
+
+// Puts the salvaged pieces back into `map` when dropped after a panic.
+pub(crate) struct Restore<'a, K: Ord, V, A: Augment<K, V>> {
+    pub(crate) map: &'a mut AVLTreeMap<K, V, A>,
+    salvage: &'a Salvage<K, V, A>,
+}
+
+impl<K: Ord, V, A: Augment<K, V>> Salvage<K, V, A> {
+    pub(crate) fn new(unshare: Unshare<K, V, A>) -> Self {
+        Self {
+            unshare,
+            own: Mutex::new(Vec::new()),
+            other: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn hold(&self) -> Held<'_, K, V, A> {
+        Held {
+            salvage: self,
+            node: None,
+            own: Default::default(),
+            other: Default::default(),
+        }
+    }
+
+    // Holds a finished piece of the map being changed.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn hold_own(&self, piece: Measured<K, V, A>) -> Held<'_, K, V, A> {
+        let mut held = self.hold();
+        held.own[0] = piece;
+        held
+    }
+
+    pub(crate) fn restore<'a>(&'a self, map: &'a mut AVLTreeMap<K, V, A>) -> Restore<'a, K, V, A> {
+        Restore { map, salvage: self }
+    }
+
+    fn keep(pieces: &Mutex<Vec<Measured<K, V, A>>>, piece: Measured<K, V, A>) {
+        if piece.0.is_some() {
+            pieces.lock().unwrap_or_else(PoisonError::into_inner).push(piece);
+        }
+    }
+
+    fn keep_node(&self, node: Arc<Node<K, V, A>>) {
+        if node.get_value().is_some() {
+            let (node, height) = join((None, 0), node, (None, 0), self.unshare);
+            Self::keep(&self.own, (node.into(), height));
+        }
+    }
+
+    // Joins pieces with disjoint key ranges in key order.
+    fn concat_sorted(&self, mut pieces: Vec<Measured<K, V, A>>) -> Measured<K, V, A> {
+        pieces.sort_by(|a, b| first_key(a).cmp(first_key(b)));
+        pieces.into_iter().fold((None, 0), |tree, piece| concat(tree, piece, self.unshare))
+    }
+
+    // The tree made of everything salvaged, if anything was. Where a key was
+    // in both maps and not yet merged, the map's own value is kept.
+    fn rebuild(&self) -> Option<Measured<K, V, A>> {
+        let own = take(&mut *self.own.lock().unwrap_or_else(PoisonError::into_inner));
+        let other = take(&mut *self.other.lock().unwrap_or_else(PoisonError::into_inner));
+        if own.is_empty() && other.is_empty() {
+            return None;
+        }
+        let (own, other) = (self.concat_sorted(own), self.concat_sorted(other));
+        let salvage = Salvage::new(self.unshare);
+        Some(union(own, other, &mut |_, own, _| own, self.unshare, &salvage))
+    }
+}
+
+fn first_key<K: Ord, V, A: Augment<K, V>>(piece: &Measured<K, V, A>) -> &K {
+    let mut node = piece.0.as_ref().unwrap();
+    while let Some(left) = node.get_left() {
+        node = left;
+    }
+    node.get_key()
+}
+
+impl<K: Ord, V, A: Augment<K, V>> Drop for Held<'_, K, V, A> {
+    fn drop(&mut self) {
+        if let Some(node) = self.node.take() {
+            self.salvage.keep_node(node);
+        }
+        for piece in &mut self.own {
+            Salvage::keep(&self.salvage.own, take(piece));
+        }
+        for piece in &mut self.other {
+            Salvage::keep(&self.salvage.other, take(piece));
+        }
+    }
+}
+
+impl<K: Ord, V, A: Augment<K, V>> Drop for Restore<'_, K, V, A> {
+    fn drop(&mut self) {
+        if let Some((root, _)) = self.salvage.rebuild() {
+            self.map.set_tree(root);
+        }
+    }
+}
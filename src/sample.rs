@@ -0,0 +1,77 @@
+#![forbid(unsafe_code)]
+
+use crate::augment::Augment;
+use crate::tree::AVLTreeMap;
+use rand::distributions::uniform::SampleUniform;
+use rand::seq::index;
+use rand::Rng;
+use std::borrow::Borrow;
+use std::cmp::Ordering::Greater;
+use std::ops::{Add, RangeBounds, Sub};
+
+impl<K: Ord, V, A: Augment<K, V>> AVLTreeMap<K, V, A> {
+    // A uniformly random entry.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<(&K, &V)> {
+        if self.is_empty() {
+            return None;
+        }
+        self.nth_key_value(rng.gen_range(0..self.len()))
+    }
+
+    // A uniformly random entry among those with keys in `range`.
+    pub fn sample_in_range<Q, B, R>(&self, range: B, rng: &mut R) -> Option<(&K, &V)>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+            B: RangeBounds<Q>,
+            R: Rng + ?Sized,
+    {
+        let ranks = self.rank_range(range);
+        if ranks.is_empty() {
+            return None;
+        }
+        self.nth_key_value(rng.gen_range(ranks))
+    }
+
+    // `k` distinct entries, or every entry if there are fewer, in random
+    // order.
+    pub fn sample_k_without_replacement<R: Rng + ?Sized>(&self, k: usize, rng: &mut R) -> Vec<(&K, &V)> {
+        index::sample(rng, self.len(), k.min(self.len()))
+            .into_iter()
+            .map(|rank| self.nth_key_value(rank).unwrap())
+            .collect()
+    }
+
+    // An entry drawn with probability proportional to its weight, where the
+    // augment sums the weights. `None` if the total weight is zero.
+    pub fn sample_weighted<W, R>(&self, rng: &mut R) -> Option<(&K, &V)>
+        where
+            A: Augment<K, V, Summary = W>,
+            W: SampleUniform + PartialOrd + Clone + Add<Output = W> + Sub<Output = W>,
+            R: Rng + ?Sized,
+    {
+        let total = self.summary();
+        if total.partial_cmp(&A::identity()) != Some(Greater) {
+            return None;
+        }
+        let mut target = rng.gen_range(A::identity()..total);
+        let mut node = self.root();
+        let mut passed = None;
+        while let Some(node_ref) = node {
+            let left = node_ref.get_left().as_ref().map_or_else(A::identity, |left| left.summary().clone());
+            let (key, value) = node_ref.get_key_value();
+            let own = A::lift(key, value);
+            if target < left {
+                node = node_ref.get_left();
+            } else if target < left.clone() + own.clone() {
+                return Some((key, value));
+            } else {
+                target = target - left - own;
+                passed = Some((key, value));
+                node = node_ref.get_right();
+            }
+        }
+        // Only float rounding gets here. The last entry passed is the closest.
+        passed
+    }
+}
@@ -0,0 +1,163 @@
+#![forbid(unsafe_code)]
+
+use crate::augment::Augment;
+use crate::node::Factor::{Balanced, LeftHeavy, RightHeavy};
+use crate::node::{Link, Node};
+use crate::tree::{update_count, AVLTreeMap, Measured};
+use serde::de::{Error, MapAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::marker::PhantomData;
+
+// Written as an ordered map, so the encoding does not depend on the shape.
+impl<K: Ord + Serialize, V: Serialize, A: Augment<K, V>> Serialize for AVLTreeMap<K, V, A> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (key, value) in self {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+struct MapVisitor<K, V, A>(PhantomData<(K, V, A)>);
+
+impl<'de, K, V, A> Visitor<'de> for MapVisitor<K, V, A>
+    where
+        K: Ord + Deserialize<'de>,
+        V: Deserialize<'de>,
+        A: Augment<K, V>,
+{
+    type Value = AVLTreeMap<K, V, A>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map")
+    }
+
+    // Input written by `serialize` is already sorted and is built in O(n).
+    // Anything else is sorted first. Of equal keys the last wins.
+    fn visit_map<M: MapAccess<'de>>(self, mut access: M) -> Result<Self::Value, M::Error> {
+        let mut entries = Vec::with_capacity(access.size_hint().unwrap_or(0).min(1 << 16));
+        while let Some(entry) = access.next_entry()? {
+            entries.push(entry);
+        }
+        if !entries.is_sorted_by(|a: &(K, V), b| a.0 <= b.0) {
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        Ok(AVLTreeMap::from_sorted_vec(entries))
+    }
+}
+
+impl<'de, K, V, A> Deserialize<'de> for AVLTreeMap<K, V, A>
+    where
+        K: Ord + Deserialize<'de>,
+        V: Deserialize<'de>,
+        A: Augment<K, V>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(MapVisitor(PhantomData))
+    }
+}
+
+// Encodes a map node by node, with the balance factors, so that the restored
+// tree has exactly the same shape and nothing needs rebalancing. Serialize
+// `ShapePreserving(&map)` and deserialize `ShapePreserving<AVLTreeMap<..>>`.
+// The nodes are written in preorder as `(left subtree size, balance, key,
+// value)`, and the shape, the balance factors and the key order are checked
+// when reading them back.
+pub struct ShapePreserving<T>(pub T);
+
+fn write_preorder<K, V, A, S>(node: &Link<K, V, A>, seq: &mut S) -> Result<(), S::Error>
+    where
+        K: Ord + Serialize,
+        V: Serialize,
+        A: Augment<K, V>,
+        S: SerializeSeq,
+{
+    let Some(node) = node else {
+        return Ok(());
+    };
+    let (key, value) = node.get_key_value();
+    seq.serialize_element(&(node.left_count, node.balance().as_int(), key, value))?;
+    write_preorder(node.get_left(), seq)?;
+    write_preorder(node.get_right(), seq)
+}
+
+impl<K: Ord + Serialize, V: Serialize, A: Augment<K, V>> Serialize for ShapePreserving<&AVLTreeMap<K, V, A>> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+        write_preorder(self.0.root(), &mut seq)?;
+        seq.end()
+    }
+}
+
+impl<K: Ord + Serialize, V: Serialize, A: Augment<K, V>> Serialize for ShapePreserving<AVLTreeMap<K, V, A>> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ShapePreserving(&self.0).serialize(serializer)
+    }
+}
+
+type Record<K, V> = (usize, i8, K, V);
+
+// Rebuilds the subtree of the next `len` records and returns it with its
+// height. `depth` bounds the recursion, as no AVL tree of `n` nodes is
+// taller than about 1.44 log2(n).
+fn restore<K: Ord, V, A: Augment<K, V>, I>(records: &mut I, len: usize, depth: u32) -> Result<Measured<K, V, A>, String>
+    where
+        I: Iterator<Item = Record<K, V>>,
+{
+    if len == 0 {
+        return Ok((None, 0));
+    }
+    if depth == 0 {
+        return Err("tree is too deep to be balanced".into());
+    }
+    let (left_len, balance, key, value) = records.next().ok_or("missing node")?;
+    if left_len >= len {
+        return Err(format!("left subtree (is {left_len}) should be smaller than the tree (is {len})"));
+    }
+    let (left, left_height) = restore(records, left_len, depth - 1)?;
+    let (right, right_height) = restore(records, len - left_len - 1, depth - 1)?;
+    let factor = match left_height as isize - right_height as isize {
+        1 => LeftHeavy,
+        0 => Balanced,
+        -1 => RightHeavy,
+        _ => return Err("subtree heights differ by more than one".into()),
+    };
+    if factor.as_int() != balance {
+        return Err(format!("balance factor (is {balance}) should be {}", factor.as_int()));
+    }
+    let mut node = Node::new(key, value);
+    node.set_balance(factor);
+    node.set_left(left);
+    node.set_right(right);
+    update_count(&mut node);
+    Ok((node.into(), left_height.max(right_height) + 1))
+}
+
+impl<'de, K, V, A> Deserialize<'de> for ShapePreserving<AVLTreeMap<K, V, A>>
+    where
+        K: Ord + Deserialize<'de>,
+        V: Deserialize<'de>,
+        A: Augment<K, V>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let records: Vec<Record<K, V>> = Vec::deserialize(deserializer)?;
+        let len = records.len();
+        let depth = 2 * (usize::BITS - len.leading_zeros()) + 2;
+        let (root, _) = restore(&mut records.into_iter(), len, depth).map_err(D::Error::custom)?;
+        let mut map = AVLTreeMap::with_augment();
+        map.set_tree(root);
+        let mut keys = map.iter().map(|(key, _)| key);
+        if let Some(mut previous) = keys.next() {
+            for key in keys {
+                if previous >= key {
+                    return Err(D::Error::custom("keys should be strictly increasing"));
+                }
+                previous = key;
+            }
+        }
+        Ok(ShapePreserving(map))
+    }
+}
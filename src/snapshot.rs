@@ -0,0 +1,312 @@
+#![forbid(unsafe_code)]
+
+// A self-checking binary image of a map. All integers are little-endian.
+//
+//   header   magic "AVLTSNAP", version: u32, entry count: u64
+//   blocks   record count: u32, payload length: u32, payload, CRC-32 of payload: u32
+//   end      a block header with record count 0 and payload length 0
+//   footer   entry count: u64, block count: u32, magic "PANSTLVA"
+//
+// A payload is its records back to back, each a key and a value written by
+// their `Codec` as length: u32 followed by that many bytes. Keys are strictly
+// increasing across the whole snapshot.
+
+use crate::augment::Augment;
+use crate::tree::AVLTreeMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+const MAGIC: [u8; 8] = *b"AVLTSNAP";
+const FOOTER_MAGIC: [u8; 8] = *b"PANSTLVA";
+const VERSION: u32 = 1;
+// Blocks are closed once their payload reaches this size.
+const BLOCK_BYTES: usize = 1 << 16;
+
+// Turns keys and values into bytes and back. `decode` gets exactly the bytes
+// `encode` appended and returns `None` if they do not make a value.
+pub trait Codec: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+
+    fn decode(bytes: &[u8]) -> Option<Self>;
+}
+
+macro_rules! impl_codec {
+    ($($int:ty),*) => {
+        $(
+            impl Codec for $int {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn decode(bytes: &[u8]) -> Option<Self> {
+                    Some(Self::from_le_bytes(bytes.try_into().ok()?))
+                }
+            }
+        )*
+    };
+}
+
+impl_codec!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128, f32, f64);
+
+impl Codec for () {
+    fn encode(&self, _: &mut Vec<u8>) {}
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        bytes.is_empty().then_some(())
+    }
+}
+
+impl Codec for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0] => Some(false),
+            [1] => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl Codec for Vec<u8> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        Some(bytes.to_vec())
+    }
+}
+
+impl Codec for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+// A tag byte, 0 for `None` and 1 for `Some`, then the value if any.
+impl<T: Codec> Codec for Option<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Some(value) => {
+                out.push(1);
+                value.encode(out);
+            }
+            None => out.push(0),
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        match bytes.split_first()? {
+            (0, []) => Some(None),
+            (1, rest) => T::decode(rest).map(Some),
+            _ => None,
+        }
+    }
+}
+
+// Why a snapshot could not be read. Entries are counted from 0 in key order.
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u32),
+    Truncated,
+    Checksum { block: u32 },
+    BadRecord { entry: u64 },
+    Unsorted { entry: u64 },
+    CountMismatch { expected: u64, found: u64 },
+    BadFooter,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "snapshot I/O failed: {error}"),
+            Self::BadMagic => f.write_str("not a snapshot"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported snapshot version {version}"),
+            Self::Truncated => f.write_str("snapshot is truncated"),
+            Self::Checksum { block } => write!(f, "checksum mismatch in block {block}"),
+            Self::BadRecord { entry } => write!(f, "entry {entry} is malformed"),
+            Self::Unsorted { entry } => write!(f, "entry {entry} is out of key order"),
+            Self::CountMismatch { expected, found } => {
+                write!(f, "snapshot should hold {expected} entries, found {found}")
+            }
+            Self::BadFooter => f.write_str("snapshot footer is malformed"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::UnexpectedEof => Self::Truncated,
+            _ => Self::Io(error),
+        }
+    }
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { 0xEDB8_8320 ^ (crc >> 1) } else { crc >> 1 };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+};
+
+// CRC-32 as used by zip and PNG.
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    crc32_extend(0, bytes)
+}
+
+// The CRC-32 of some bytes followed by `bytes`, given the CRC-32 of the
+// former, so that long streams can be checked piece by piece.
+pub(crate) fn crc32_extend(crc: u32, bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!crc, |crc, byte| CRC_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+pub(crate) fn put_field<T: Codec>(item: &T, out: &mut Vec<u8>) -> io::Result<()> {
+    let start = out.len();
+    out.extend_from_slice(&[0; 4]);
+    item.encode(out);
+    let len = u32::try_from(out.len() - start - 4)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "encoded item is over 4 GiB"))?;
+    out[start..start + 4].copy_from_slice(&len.to_le_bytes());
+    Ok(())
+}
+
+// Decodes the length-prefixed item at the front of `bytes` and moves past it.
+pub(crate) fn take_field<T: Codec>(bytes: &mut &[u8]) -> Option<T> {
+    let (len, rest) = bytes.split_first_chunk::<4>()?;
+    let len = u32::from_le_bytes(*len) as usize;
+    if rest.len() < len {
+        return None;
+    }
+    let (field, rest) = rest.split_at(len);
+    *bytes = rest;
+    T::decode(field)
+}
+
+pub(crate) fn read_array<R: Read, const N: usize>(reader: &mut R) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn write_block<W: Write>(writer: &mut W, records: u32, payload: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(payload.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "snapshot block is over 4 GiB"))?;
+    writer.write_all(&records.to_le_bytes())?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(payload)?;
+    writer.write_all(&crc32(payload).to_le_bytes())
+}
+
+impl<K: Ord + Codec, V: Codec, A: Augment<K, V>> AVLTreeMap<K, V, A> {
+    // Writes the map in the snapshot format above, one block at a time.
+    pub fn write_snapshot<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(self.len() as u64).to_le_bytes())?;
+        let (mut payload, mut records, mut blocks) = (Vec::new(), 0, 0u32);
+        for (key, value) in self {
+            put_field(key, &mut payload)?;
+            put_field(value, &mut payload)?;
+            records += 1;
+            if payload.len() >= BLOCK_BYTES {
+                write_block(writer, records, &payload)?;
+                (records, blocks) = (0, blocks + 1);
+                payload.clear();
+            }
+        }
+        if records > 0 {
+            write_block(writer, records, &payload)?;
+            blocks += 1;
+        }
+        write_block(writer, 0, &[])?;
+        writer.write_all(&(self.len() as u64).to_le_bytes())?;
+        writer.write_all(&blocks.to_le_bytes())?;
+        writer.write_all(&FOOTER_MAGIC)
+    }
+
+    // Reads a snapshot written by `write_snapshot`, checking every block and
+    // the key order, and builds the tree bottom-up in O(n).
+    pub fn read_snapshot<R: Read>(mut reader: R) -> Result<Self, SnapshotError> {
+        if read_array(&mut reader)? != MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+        let version = u32::from_le_bytes(read_array(&mut reader)?);
+        if version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let expected = u64::from_le_bytes(read_array(&mut reader)?);
+        // The count is not trusted for more than a modest reservation.
+        let mut entries: Vec<(K, V)> = Vec::with_capacity(expected.min(1 << 16) as usize);
+        let mut blocks = 0;
+        loop {
+            let records = u32::from_le_bytes(read_array(&mut reader)?);
+            let len = u32::from_le_bytes(read_array(&mut reader)?);
+            let mut payload = Vec::new();
+            reader.by_ref().take(len as u64).read_to_end(&mut payload)?;
+            if payload.len() != len as usize {
+                return Err(SnapshotError::Truncated);
+            }
+            if u32::from_le_bytes(read_array(&mut reader)?) != crc32(&payload) {
+                return Err(SnapshotError::Checksum { block: blocks });
+            }
+            if records == 0 {
+                if len != 0 {
+                    return Err(SnapshotError::BadRecord { entry: entries.len() as u64 });
+                }
+                break;
+            }
+            let mut rest = payload.as_slice();
+            for _ in 0..records {
+                let entry = entries.len() as u64;
+                let key = take_field(&mut rest).ok_or(SnapshotError::BadRecord { entry })?;
+                let value = take_field(&mut rest).ok_or(SnapshotError::BadRecord { entry })?;
+                if entries.last().is_some_and(|(last, _)| *last >= key) {
+                    return Err(SnapshotError::Unsorted { entry });
+                }
+                entries.push((key, value));
+            }
+            if !rest.is_empty() {
+                return Err(SnapshotError::BadRecord { entry: entries.len() as u64 });
+            }
+            blocks += 1;
+        }
+        let found = entries.len() as u64;
+        if found != expected {
+            return Err(SnapshotError::CountMismatch { expected, found });
+        }
+        let footer_count = u64::from_le_bytes(read_array(&mut reader)?);
+        let footer_blocks = u32::from_le_bytes(read_array(&mut reader)?);
+        if footer_count != found || footer_blocks != blocks || read_array(&mut reader)? != FOOTER_MAGIC {
+            return Err(SnapshotError::BadFooter);
+        }
+        Ok(Self::from_sorted_vec(entries))
+    }
+}
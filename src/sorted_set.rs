@@ -0,0 +1,200 @@
+#![forbid(unsafe_code)]
+
+use crate::iter::Iter;
+use crate::tree::AVLTreeMap;
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::iter::FusedIterator;
+use std::ops::{Add, Bound, RangeBounds};
+
+// Members ordered by score, then by member. The hash index finds a member's
+// score, and the tree turns `(score, member)` into a rank.
+pub struct AVLSortedSet<M: Hash + Eq + Ord + Clone, S: Ord + Clone> {
+    scores: HashMap<M, S>,
+    order: AVLTreeMap<(S, M), ()>,
+}
+
+impl<M: Hash + Eq + Ord + Clone, S: Ord + Clone> Default for AVLSortedSet<M, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M: Hash + Eq + Ord + Clone, S: Ord + Clone> Clone for AVLSortedSet<M, S> {
+    fn clone(&self) -> Self {
+        Self {
+            scores: self.scores.clone(),
+            order: self.order.clone(),
+        }
+    }
+}
+
+impl<M: Hash + Eq + Ord + Clone, S: Ord + Clone> AVLSortedSet<M, S> {
+    pub fn new() -> Self {
+        Self {
+            scores: HashMap::new(),
+            order: AVLTreeMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    // Sets the score of `member`. Returns whether the member is new.
+    pub fn zadd(&mut self, member: M, score: S) -> bool {
+        let added = match self.scores.insert(member.clone(), score.clone()) {
+            Some(old) => {
+                self.order.remove(&(old, member.clone()));
+                false
+            }
+            None => true,
+        };
+        self.order.insert((score, member), ());
+        added
+    }
+
+    // Adds `delta` to the score of `member`, which starts from the default
+    // score if absent, and returns the new score.
+    pub fn zincrby(&mut self, member: M, delta: S) -> S
+        where
+            S: Add<Output = S> + Default,
+    {
+        let score = match self.scores.get(&member) {
+            Some(old) => {
+                let old = old.clone();
+                self.order.remove(&(old.clone(), member.clone()));
+                old + delta
+            }
+            None => S::default() + delta,
+        };
+        self.scores.insert(member.clone(), score.clone());
+        self.order.insert((score.clone(), member), ());
+        score
+    }
+
+    pub fn zrem<Q>(&mut self, member: &Q) -> bool
+        where
+            M: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+    {
+        match self.scores.remove_entry(member) {
+            Some((member, score)) => {
+                self.order.remove(&(score, member));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn zscore<Q>(&self, member: &Q) -> Option<&S>
+        where
+            M: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+    {
+        self.scores.get(member)
+    }
+
+    // Position of `member` counting from the lowest score.
+    pub fn zrank<Q>(&self, member: &Q) -> Option<usize>
+        where
+            M: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+    {
+        let (member, score) = self.scores.get_key_value(member)?;
+        Some(self.order.partition_point(|(s, m)| (s, m) < (score, member)))
+    }
+
+    // Position of `member` counting from the highest score.
+    pub fn zrevrank<Q>(&self, member: &Q) -> Option<usize>
+        where
+            M: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+    {
+        self.zrank(member).map(|rank| self.len() - 1 - rank)
+    }
+
+    // Members at the ranks in `ranks`, lowest score first. Ranks past the end
+    // are ignored.
+    pub fn zrange_by_rank<R: RangeBounds<usize>>(&self, ranks: R) -> SortedSetIter<'_, M, S> {
+        let len = self.len();
+        let start = match ranks.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match ranks.end_bound() {
+            Bound::Included(end) => end.saturating_add(1),
+            Bound::Excluded(end) => *end,
+            Bound::Unbounded => len,
+        };
+        self.ranks(start.min(len), end.min(len))
+    }
+
+    pub fn zrange_by_score<R: RangeBounds<S>>(&self, scores: R) -> SortedSetIter<'_, M, S> {
+        let (start, end) = self.score_ranks(scores);
+        self.ranks(start, end)
+    }
+
+    pub fn zcount<R: RangeBounds<S>>(&self, scores: R) -> usize {
+        let (start, end) = self.score_ranks(scores);
+        end.saturating_sub(start)
+    }
+
+    pub fn iter(&self) -> SortedSetIter<'_, M, S> {
+        self.ranks(0, self.len())
+    }
+
+    fn score_ranks<R: RangeBounds<S>>(&self, scores: R) -> (usize, usize) {
+        let start = match scores.start_bound() {
+            Bound::Included(low) => self.order.partition_point(|(score, _)| score < low),
+            Bound::Excluded(low) => self.order.partition_point(|(score, _)| score <= low),
+            Bound::Unbounded => 0,
+        };
+        let end = match scores.end_bound() {
+            Bound::Included(high) => self.order.partition_point(|(score, _)| score <= high),
+            Bound::Excluded(high) => self.order.partition_point(|(score, _)| score < high),
+            Bound::Unbounded => self.len(),
+        };
+        (start, end)
+    }
+
+    fn ranks(&self, start: usize, end: usize) -> SortedSetIter<'_, M, S> {
+        SortedSetIter {
+            iter: Iter::new(self.order.root(), start, end.saturating_sub(start)),
+        }
+    }
+}
+
+pub struct SortedSetIter<'a, M, S> {
+    iter: Iter<'a, (S, M), ()>,
+}
+
+impl<'a, M: Ord, S: Ord> Clone for SortedSetIter<'a, M, S> {
+    fn clone(&self) -> Self {
+        Self {
+            iter: self.iter.clone(),
+        }
+    }
+}
+
+impl<'a, M: Ord, S: Ord> Iterator for SortedSetIter<'a, M, S> {
+    type Item = (&'a M, &'a S);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|((score, member), _)| (member, score))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, M: Ord, S: Ord> ExactSizeIterator for SortedSetIter<'a, M, S> {}
+
+impl<'a, M: Ord, S: Ord> FusedIterator for SortedSetIter<'a, M, S> {}
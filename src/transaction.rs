@@ -0,0 +1,98 @@
+#![forbid(unsafe_code)]
+
+use crate::augment::Augment;
+use crate::iter::Iter;
+use crate::tree::AVLTreeMap;
+use std::borrow::Borrow;
+use std::ops::RangeBounds;
+
+// Changes to a map that take effect all at once. A transaction works on an
+// O(1) clone of the map, and a change copies only the nodes on its path,
+// so the map itself is never touched until the transaction commits.
+pub struct Transaction<K: Ord, V, A: Augment<K, V> = ()> {
+    map: AVLTreeMap<K, V, A>,
+}
+
+impl<K: Ord + Clone, V: Clone, A: Augment<K, V>> AVLTreeMap<K, V, A> {
+    // Runs `f` on a transaction over this map and applies its changes if it
+    // returns `Ok`. On `Err`, or if `f` panics, the map stays as it was.
+    pub fn transaction<F, R, E>(&mut self, f: F) -> Result<R, E>
+        where
+            F: FnOnce(&mut Transaction<K, V, A>) -> Result<R, E>,
+    {
+        let mut transaction = Transaction { map: self.clone() };
+        let result = f(&mut transaction)?;
+        *self = transaction.map;
+        Ok(result)
+    }
+}
+
+impl<K: Ord + Clone, V: Clone, A: Augment<K, V>> Transaction<K, V, A> {
+    // The map with the transaction's changes so far.
+    pub fn map(&self) -> &AVLTreeMap<K, V, A> {
+        &self.map
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.map.get(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.map.contains_key(key)
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.map.insert(key, value)
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.map.remove(key)
+    }
+
+    pub fn range<Q, R>(&self, range: R) -> Iter<'_, K, V, A>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+            R: RangeBounds<Q>,
+    {
+        self.map.range(range)
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V, A> {
+        self.map.iter()
+    }
+
+    // Runs `f` within this transaction and undoes just its changes if it
+    // returns `Err`, leaving the earlier ones in place. Savepoints nest.
+    pub fn savepoint<F, R, E>(&mut self, f: F) -> Result<R, E>
+        where
+            F: FnOnce(&mut Self) -> Result<R, E>,
+    {
+        let saved = self.map.clone();
+        let result = f(self);
+        if result.is_err() {
+            self.map = saved;
+        }
+        result
+    }
+}
@@ -1,14 +1,23 @@
 #![forbid(unsafe_code)]
 
+use crate::augment::Augment;
 use crate::node::Factor::{Balanced, LeftHeavy, RightHeavy};
-use crate::node::Node;
+use crate::iter::Iter;
+use crate::salvage::Salvage;
+use crate::node::{into_inner, make_mut, CloneFn, Link, Node, Unshare};
 use crate::tree::Direction::{Left, Right};
 use std::borrow::Borrow;
+use std::cmp::Ordering;
 use std::cmp::Ordering::{Equal, Greater, Less};
+use std::mem::{swap, take};
+use std::ops::ControlFlow::{self, Break, Continue};
+use std::ops::{Bound, Range, RangeBounds};
+use std::sync::{Arc, OnceLock};
 
-pub struct AVLTreeMap<K: Ord, V> {
-    root: Option<Box<Node<K, V>>>,
+pub struct AVLTreeMap<K: Ord, V, A: Augment<K, V> = ()> {
+    root: Link<K, V, A>,
     size: usize,
+    clone_node: OnceLock<CloneFn<K, V, A>>,
 }
 
 #[derive(Copy, Clone)]
@@ -17,17 +26,54 @@ pub enum Direction {
     Right,
 }
 
-impl<K: Ord, V> Default for AVLTreeMap<K, V> {
+impl<K: Ord, V, A: Augment<K, V>> Default for AVLTreeMap<K, V, A> {
     fn default() -> Self {
-        Self::new()
+        Self::with_augment()
+    }
+}
+
+impl<K: Ord + Clone, V: Clone, A: Augment<K, V>> Clone for AVLTreeMap<K, V, A> {
+    fn clone(&self) -> Self {
+        let clone_node = *self.clone_node.get_or_init(|| Node::clone);
+        Self {
+            root: self.root.clone(),
+            size: self.size,
+            clone_node: OnceLock::from(clone_node),
+        }
     }
 }
 
 impl<K: Ord, V> AVLTreeMap<K, V> {
     pub fn new() -> Self {
+        Self::with_augment()
+    }
+
+    // Only offered without an augment, where nothing above the value depends
+    // on it.
+    pub(crate) fn nth_value_mut(&mut self, mut index: usize) -> Option<&mut V> {
+        let unshare = self.unshare();
+        let mut node = &mut self.root;
+        while let Some(node_ref) = node {
+            let current = make_mut(node_ref, unshare);
+            match index.cmp(&current.left_count) {
+                Less => node = current.get_left_mut(),
+                Greater => {
+                    index -= current.left_count + 1;
+                    node = current.get_right_mut();
+                }
+                Equal => return current.get_value_mut(),
+            }
+        }
+        None
+    }
+}
+
+impl<K: Ord, V, A: Augment<K, V>> AVLTreeMap<K, V, A> {
+    pub fn with_augment() -> Self {
         Self {
             root: None,
             size: 0,
+            clone_node: OnceLock::new(),
         }
     }
 
@@ -39,11 +85,48 @@ impl<K: Ord, V> AVLTreeMap<K, V> {
         self.root.is_none()
     }
 
-    pub(self) fn set_root(&mut self, node: Option<Box<Node<K, V>>>) {
+    pub(self) fn set_root(&mut self, node: Link<K, V, A>) {
         self.root = node
     }
 
-    pub fn get(&self, key: &K) -> Option<&V> {
+    pub(crate) fn unshare(&self) -> Unshare<K, V, A> {
+        self.clone_node.get().copied()
+    }
+
+    // Lets `other`'s nodes be copied by this map once they end up in it.
+    pub(crate) fn inherit_clone_node(&self, other: &Self) {
+        if let Some(clone_node) = other.clone_node.get() {
+            let _ = self.clone_node.set(*clone_node);
+        }
+    }
+
+    // Hands the tree out with its height, for algorithms that take it apart,
+    // leaving the map empty.
+    pub(crate) fn take_tree(&mut self) -> Measured<K, V, A> {
+        let height = height(&self.root);
+        self.size = 0;
+        (self.root.take(), height)
+    }
+
+    pub(crate) fn set_tree(&mut self, root: Link<K, V, A>) {
+        self.size = root.as_ref().map_or(0, |node| node.left_count + node.right_count + 1);
+        self.set_root(root);
+    }
+
+    #[cfg(feature = "rayon")]
+    pub(crate) fn root_mut(&mut self) -> &mut Link<K, V, A> {
+        &mut self.root
+    }
+
+    pub(crate) fn root(&self) -> &Link<K, V, A> {
+        &self.root
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
         if let Some(node) = search(&self.root, key) {
             node.get_value()
         } else {
@@ -61,8 +144,10 @@ impl<K: Ord, V> AVLTreeMap<K, V> {
 
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
         let mut need_balance = true;
+        let unshare = self.unshare();
         let opt_v = if let Some(node) = self.root.take() {
-            let (opt_v, new_root) = insert(node, key, value, &mut need_balance);
+            let mut cmp = |key: &K, node: &Node<K, V, A>| key.cmp(node.get_key());
+            let (opt_v, new_root) = insert(node, key, value, &mut cmp, &mut need_balance, unshare);
             self.set_root(new_root.into());
             opt_v
         } else {
@@ -83,9 +168,16 @@ impl<K: Ord, V> AVLTreeMap<K, V> {
             K: Borrow<Q>,
             Q: Ord + ?Sized,
     {
+        // Searches without copying first, so a missing key leaves the nodes
+        // shared with clones.
+        if !self.contains_key(key) {
+            return None;
+        }
         let mut need_balance = true;
+        let unshare = self.unshare();
         let opt_k_v = if let Some(node) = self.root.take() {
-            let (new_root, opt_k_v) = delete_node(node.into(), key, &mut need_balance);
+            let mut cmp = |node: &Node<K, V, A>| key.cmp(node.get_key_borrowed());
+            let (new_root, opt_k_v) = delete_node(node.into(), &mut cmp, &mut need_balance, unshare);
             self.set_root(new_root);
             opt_k_v
         } else {
@@ -111,12 +203,129 @@ impl<K: Ord, V> AVLTreeMap<K, V> {
         }
     }
 
-    pub fn get_key_value(&self, key: &K) -> Option<(&K, &V)> {
+    pub(crate) fn insert_nth(&mut self, mut index: usize, key: K, value: V) {
+        let mut need_balance = true;
+        let unshare = self.unshare();
+        if let Some(node) = self.root.take() {
+            let mut cmp = |_: &K, node: &Node<K, V, A>| {
+                if index <= node.left_count {
+                    Less
+                } else {
+                    index -= node.left_count + 1;
+                    Greater
+                }
+            };
+            let (_, new_root) = insert(node, key, value, &mut cmp, &mut need_balance, unshare);
+            self.set_root(new_root.into());
+        } else {
+            self.set_root(Node::new(key, value).into());
+        }
+        self.size += 1;
+    }
+
+    pub(crate) fn remove_nth(&mut self, mut index: usize) -> Option<(K, V)> {
+        if index >= self.size {
+            return None;
+        }
+        let mut need_balance = true;
+        let unshare = self.unshare();
+        let mut cmp = by_rank(&mut index);
+        let (new_root, opt_k_v) = delete_node(self.root.take(), &mut cmp, &mut need_balance, unshare);
+        self.set_root(new_root);
+        self.size -= 1;
+        opt_k_v
+    }
+
+    // Moves the entries from rank `at` on into a new map.
+    pub(crate) fn split_off_nth(&mut self, at: usize) -> Self {
+        let unshare = self.unshare();
+        let height = height(&self.root);
+        let mut index = at;
+        let mut cmp = |node: &Node<K, V, A>| {
+            if index <= node.left_count {
+                Less
+            } else {
+                index -= node.left_count + 1;
+                Greater
+            }
+        };
+        let (left, _, right) = split(self.root.take(), height, &mut cmp, unshare);
+        let other = Self {
+            root: right.0,
+            size: self.size - at,
+            clone_node: self.clone_node.clone(),
+        };
+        self.set_root(left.0);
+        self.size = at;
+        other
+    }
+
+    // Puts every entry of `other` after the entries of `self`.
+    pub(crate) fn append_nth(&mut self, other: &mut Self) {
+        self.inherit_clone_node(other);
+        let unshare = self.unshare();
+        let left_height = height(&self.root);
+        let right_height = height(&other.root);
+        let left = (self.root.take(), left_height);
+        let right = (other.root.take(), right_height);
+        let (root, _) = concat(left, right, unshare);
+        self.set_root(root);
+        self.size += other.size;
+        other.size = 0;
+    }
+
+    pub fn update<Q, F, R>(&mut self, key: &Q, f: F) -> Option<R>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+            F: FnOnce(&mut V) -> R,
+    {
+        if !self.contains_key(key) {
+            return None;
+        }
+        let unshare = self.unshare();
+        let mut cmp = |node: &Node<K, V, A>| key.cmp(node.get_key_borrowed());
+        Some(update(&mut self.root, &mut cmp, f, unshare))
+    }
+
+    pub(crate) fn update_nth<F, R>(&mut self, mut index: usize, f: F) -> Option<R>
+        where
+            F: FnOnce(&mut V) -> R,
+    {
+        if index >= self.size {
+            return None;
+        }
+        let unshare = self.unshare();
+        let mut cmp = by_rank(&mut index);
+        Some(update(&mut self.root, &mut cmp, f, unshare))
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
         search(&self.root, key)
             .as_ref()
             .map(|node| node.get_key_value())
     }
 
+    pub fn floor_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        closest(&self.root, key, Greater)
+    }
+
+    pub fn ceiling_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        closest(&self.root, key, Less)
+    }
+
     pub fn nth_key_value(&self, mut index: usize) -> Option<(&K, &V)> {
         let mut node = &self.root;
         loop {
@@ -134,57 +343,238 @@ impl<K: Ord, V> AVLTreeMap<K, V> {
             }
         }
     }
+
+    // The entry `q` of the way through the keys, rounding down, so
+    // `quantile(0.0)` is the first and `quantile(1.0)` the last.
+    pub fn quantile(&self, q: f64) -> Option<(&K, &V)> {
+        self.nth_key_value(quantile_index(q, self.size)?)
+    }
+
+    // The lower median.
+    pub fn median(&self) -> Option<(&K, &V)> {
+        self.quantile(0.5)
+    }
+
+    // Share of the keys that are at most `key`.
+    pub fn percentile_rank<Q>(&self, key: &Q) -> Option<f64>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        if self.size == 0 {
+            return None;
+        }
+        Some(rank(&self.root, key, true) as f64 / self.size as f64)
+    }
+
+    // Number of leading keys `pred` holds for, as in `slice::partition_point`.
+    pub fn partition_point<F>(&self, mut pred: F) -> usize
+        where
+            F: FnMut(&K) -> bool,
+    {
+        let mut node = &self.root;
+        let mut below = 0;
+        while let Some(node_ref) = node {
+            if pred(node_ref.get_key()) {
+                below += node_ref.left_count + 1;
+                node = node_ref.get_right();
+            } else {
+                node = node_ref.get_left();
+            }
+        }
+        below
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V, A> {
+        Iter::new(&self.root, 0, self.size)
+    }
+
+    pub fn range<Q, R>(&self, range: R) -> Iter<'_, K, V, A>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+            R: RangeBounds<Q>,
+    {
+        let ranks = self.rank_range(range);
+        Iter::new(&self.root, ranks.start, ranks.len())
+    }
+
+    // Ranks of the keys within `range`.
+    pub(crate) fn rank_range<Q, R>(&self, range: R) -> Range<usize>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+            R: RangeBounds<Q>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(key) => rank(&self.root, key, false),
+            Bound::Excluded(key) => rank(&self.root, key, true),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(key) => rank(&self.root, key, true),
+            Bound::Excluded(key) => rank(&self.root, key, false),
+            Bound::Unbounded => self.size,
+        };
+        start..end.max(start)
+    }
+
+    pub fn summary(&self) -> A::Summary {
+        self.root
+            .as_ref()
+            .map_or_else(A::identity, |node| node.summary().clone())
+    }
+
+    pub fn fold_range<Q, R>(&self, range: R) -> A::Summary
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+            R: RangeBounds<Q>,
+    {
+        fold_range(&self.root, &range, false, false)
+    }
+
+    // Keeps the entries `f` returns true for, visiting them in key order.
+    // If `f` panics, the map keeps every entry it has not removed so far.
+    pub fn retain<F>(&mut self, mut f: F)
+        where
+            F: FnMut(&K, &mut V) -> bool,
+    {
+        let unshare = self.unshare();
+        let tree = self.take_tree();
+        let salvage = Salvage::new(unshare);
+        let restore = salvage.restore(self);
+        let (root, _) = retain(tree, &mut f, unshare, &salvage);
+        restore.map.set_tree(root);
+    }
+
+    // Moves every entry of `other` into `self`. For a key in both, the value
+    // becomes `f(key, own value, other value)`. If `f` panics, `self` ends
+    // up with the entries of both maps but the one being merged, and its own
+    // values for the keys not merged yet.
+    pub fn union_with<F>(&mut self, mut other: Self, mut f: F)
+        where
+            F: FnMut(&K, V, V) -> V,
+    {
+        self.inherit_clone_node(&other);
+        let unshare = self.unshare();
+        let (left, right) = (self.take_tree(), other.take_tree());
+        let salvage = Salvage::new(unshare);
+        let restore = salvage.restore(self);
+        let (root, _) = union(left, right, &mut f, unshare, &salvage);
+        restore.map.set_tree(root);
+    }
+
+    // Moves the entries with keys from `key` on into a new map, in O(log n).
+    pub fn split_off<Q>(&mut self, key: &Q) -> Self
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        let at = self.rank_range((Bound::Unbounded, Bound::Excluded(key))).end;
+        self.split_off_nth(at)
+    }
+
+    // Moves every entry of `other` into `self`, the values of `other` winning
+    // for keys in both. If all of `other` comes after `self`, the two trees
+    // are joined in O(log n).
+    pub fn append(&mut self, other: &mut Self) {
+        let after = match (self.nth_key_value(self.size.wrapping_sub(1)), other.nth_key_value(0)) {
+            (Some((last, _)), Some((first, _))) => last < first,
+            _ => true,
+        };
+        if after {
+            self.append_nth(other);
+        } else {
+            self.union_with(other.split_off_nth(0), |_, _, value| value);
+        }
+    }
+
+    // Builds a map from entries sorted by key. Of equal keys the last wins.
+    pub(crate) fn from_sorted_vec(mut entries: Vec<(K, V)>) -> Self {
+        dedup_last(&mut entries);
+        let len = entries.len();
+        let mut map = Self::with_augment();
+        map.set_tree(build(&mut entries.into_iter(), len));
+        map
+    }
 }
 
-type TupleOption<K, V> = (Option<Box<Node<K, V>>>, Option<(K, V)>);
+// Sorts the entries and builds the tree bottom-up in one pass.
+impl<K: Ord, V, A: Augment<K, V>> FromIterator<(K, V)> for AVLTreeMap<K, V, A> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut entries: Vec<_> = iter.into_iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        Self::from_sorted_vec(entries)
+    }
+}
 
-fn delete_node<K: Ord, V, Q>(
-    node: Option<Box<Node<K, V>>>,
-    key: &Q,
+impl<'a, K: Ord, V, A: Augment<K, V>> IntoIterator for &'a AVLTreeMap<K, V, A> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+type TupleOption<K, V, A> = (Link<K, V, A>, Option<(K, V)>);
+
+// `cmp` tells on which side of a node the entry to delete is, like comparing
+// its key would. It is called once per node on the path.
+fn delete_node<K, V, A: Augment<K, V>, F>(
+    node: Link<K, V, A>,
+    cmp: &mut F,
     need_balance: &mut bool,
-) -> TupleOption<K, V>
+    unshare: Unshare<K, V, A>,
+) -> TupleOption<K, V, A>
     where
-        K: Borrow<Q>,
-        Q: Ord + ?Sized,
+        K: Ord,
+        F: FnMut(&Node<K, V, A>) -> Ordering,
 {
     if let Some(mut current_node) = node {
         let (opt, child);
-        match key.cmp(current_node.get_key_borrowed()) {
+        let current = make_mut(&mut current_node, unshare);
+        match cmp(current) {
             Less => {
-                (child, opt) = delete_node(current_node.take_left(), key, need_balance);
-                current_node.set_left(child);
+                (child, opt) = delete_node(current.take_left(), cmp, need_balance, unshare);
+                current.set_left(child);
                 if *need_balance {
-                    match current_node.balance() {
-                        Balanced => current_node.set_balance(RightHeavy),
-                        LeftHeavy => current_node.set_balance(Balanced),
+                    match current.balance() {
+                        Balanced => current.set_balance(RightHeavy),
+                        LeftHeavy => current.set_balance(Balanced),
                         RightHeavy => {
-                            let right_child = current_node.take_right().unwrap();
-                            current_node = rebalance(current_node, right_child, Right);
+                            let right_child = current.take_right().unwrap();
+                            current_node = rebalance(current_node, right_child, Right, unshare);
+                            *need_balance = *current_node.balance() == Balanced;
+                            return (current_node.into(), opt);
                         }
                     }
-                    *need_balance = *current_node.balance() == Balanced;
+                    *need_balance = *current.balance() == Balanced;
                 }
-                update_count(&mut current_node);
+                update_count(current);
                 (current_node.into(), opt)
             }
             Greater => {
-                (child, opt) = delete_node(current_node.take_right(), key, need_balance);
-                current_node.set_right(child);
+                (child, opt) = delete_node(current.take_right(), cmp, need_balance, unshare);
+                current.set_right(child);
                 if *need_balance {
-                    match current_node.balance() {
-                        Balanced => current_node.set_balance(LeftHeavy),
-                        RightHeavy => current_node.set_balance(Balanced),
+                    match current.balance() {
+                        Balanced => current.set_balance(LeftHeavy),
+                        RightHeavy => current.set_balance(Balanced),
                         LeftHeavy => {
-                            let left_child = current_node.take_left().unwrap();
-                            current_node = rebalance(current_node, left_child, Left);
+                            let left_child = current.take_left().unwrap();
+                            current_node = rebalance(current_node, left_child, Left, unshare);
+                            *need_balance = *current_node.balance() == Balanced;
+                            return (current_node.into(), opt);
                         }
                     }
-                    *need_balance = *current_node.balance() == Balanced;
+                    *need_balance = *current.balance() == Balanced;
                 }
-                update_count(&mut current_node);
+                update_count(current);
                 (current_node.into(), opt)
             }
-            Equal => remove_node(current_node, need_balance),
+            Equal => remove_node(current_node, need_balance, unshare),
         }
     } else {
         *need_balance = false;
@@ -192,74 +582,92 @@ fn delete_node<K: Ord, V, Q>(
     }
 }
 
-fn remove_node<K: Ord, V>(mut node: Box<Node<K, V>>, need_balance: &mut bool) -> TupleOption<K, V> {
+fn remove_node<K: Ord, V, A: Augment<K, V>>(
+    node: Arc<Node<K, V, A>>,
+    need_balance: &mut bool,
+    unshare: Unshare<K, V, A>,
+) -> TupleOption<K, V, A> {
     match (node.get_left().is_none(), node.get_right().is_none()) {
-        (true, true) => (None, Some(node.get_key_value_tuple())),
-        (true, false) => (node.take_right(), Some(node.get_key_value_tuple())),
-        (false, true) => (node.take_left(), Some(node.get_key_value_tuple())),
-        (_, _) => find_closest(node, need_balance),
+        (true, true) => (None, Some(into_inner(node, unshare).get_key_value_tuple())),
+        (true, false) => {
+            let mut node = into_inner(node, unshare);
+            (node.take_right(), Some(node.get_key_value_tuple()))
+        }
+        (false, true) => {
+            let mut node = into_inner(node, unshare);
+            (node.take_left(), Some(node.get_key_value_tuple()))
+        }
+        (_, _) => find_closest(node, need_balance, unshare),
     }
 }
 
-fn find_closest<K: Ord, V>(
-    mut node: Box<Node<K, V>>,
+fn find_closest<K: Ord, V, A: Augment<K, V>>(
+    mut node: Arc<Node<K, V, A>>,
     need_balance: &mut bool,
-) -> TupleOption<K, V> {
-    let (child, opt_k_v) = match node.balance() {
-        LeftHeavy | Balanced => find_predecessor(node.take_left(), need_balance),
-        RightHeavy => find_successor(node.take_right(), need_balance),
+    unshare: Unshare<K, V, A>,
+) -> TupleOption<K, V, A> {
+    let current = make_mut(&mut node, unshare);
+    let (child, opt_k_v) = match current.balance() {
+        LeftHeavy | Balanced => find_predecessor(current.take_left(), need_balance, unshare),
+        RightHeavy => find_successor(current.take_right(), need_balance, unshare),
     };
 
     if let Some(tuple) = opt_k_v {
-        let old_key_value = node.replace_key_value(tuple);
-        match node.balance() {
-            LeftHeavy | Balanced => node.set_left(child),
-            RightHeavy => node.set_right(child),
+        let old_key_value = current.replace_key_value(tuple);
+        match current.balance() {
+            LeftHeavy | Balanced => current.set_left(child),
+            RightHeavy => current.set_right(child),
         };
         if *need_balance {
-            match node.balance() {
-                LeftHeavy | RightHeavy => node.set_balance(Balanced),
-                Balanced => node.set_balance(RightHeavy),
+            match current.balance() {
+                LeftHeavy | RightHeavy => current.set_balance(Balanced),
+                Balanced => current.set_balance(RightHeavy),
             }
-            *need_balance = *node.balance() == Balanced;
+            *need_balance = *current.balance() == Balanced;
         };
-        update_count(&mut node);
+        update_count(current);
         (node.into(), Some(old_key_value))
     } else {
-        let key_value = node.replace_key_value(child.unwrap().get_key_value_tuple());
-        update_count(&mut node);
+        let child = into_inner(child.unwrap(), unshare);
+        let key_value = current.replace_key_value(child.get_key_value_tuple());
+        update_count(current);
         (None, Some(key_value))
     }
 }
 
-fn find_successor<K: Ord, V>(
-    node: Option<Box<Node<K, V>>>,
+fn find_successor<K: Ord, V, A: Augment<K, V>>(
+    node: Link<K, V, A>,
     need_balance: &mut bool,
-) -> TupleOption<K, V> {
+    unshare: Unshare<K, V, A>,
+) -> TupleOption<K, V, A> {
     if let Some(mut current_node) = node {
-        let (l_child, opt) = find_successor(current_node.take_left(), need_balance);
+        let current = make_mut(&mut current_node, unshare);
+        let (l_child, opt) = find_successor(current.take_left(), need_balance, unshare);
         match &opt {
             None => {
-                if let Some(right_child) = current_node.take_right() {
-                    (right_child.into(), Some(current_node.get_key_value_tuple()))
+                let mut current = into_inner(current_node, unshare);
+                if let Some(right_child) = current.take_right() {
+                    (right_child.into(), Some(current.get_key_value_tuple()))
                 } else {
-                    (None, Some(current_node.get_key_value_tuple()))
+                    (None, Some(current.get_key_value_tuple()))
                 }
             }
             Some(_) => {
-                current_node.set_left(l_child);
+                current.set_left(l_child);
                 if *need_balance {
-                    match current_node.balance() {
-                        Balanced => current_node.set_balance(RightHeavy),
-                        LeftHeavy => current_node.set_balance(Balanced),
+                    match current.balance() {
+                        Balanced => current.set_balance(RightHeavy),
+                        LeftHeavy => current.set_balance(Balanced),
                         RightHeavy => {
-                            let right_child = current_node.take_right().unwrap();
-                            current_node = rebalance(current_node, right_child, Right);
+                            let right_child = current.take_right().unwrap();
+                            current_node = rebalance(current_node, right_child, Right, unshare);
+                            *need_balance = *current_node.balance() == Balanced;
+                            return (current_node.into(), opt);
                         }
                     }
-                    *need_balance = *current_node.balance() == Balanced;
+                    *need_balance = *current.balance() == Balanced;
                 }
-                update_count(&mut current_node);
+                update_count(current);
                 (current_node.into(), opt)
             }
         }
@@ -268,34 +676,39 @@ fn find_successor<K: Ord, V>(
     }
 }
 
-fn find_predecessor<K: Ord, V>(
-    node: Option<Box<Node<K, V>>>,
+fn find_predecessor<K: Ord, V, A: Augment<K, V>>(
+    node: Link<K, V, A>,
     need_balance: &mut bool,
-) -> TupleOption<K, V> {
+    unshare: Unshare<K, V, A>,
+) -> TupleOption<K, V, A> {
     if let Some(mut current_node) = node {
-        let (r_child, opt) = find_predecessor(current_node.take_right(), need_balance);
+        let current = make_mut(&mut current_node, unshare);
+        let (r_child, opt) = find_predecessor(current.take_right(), need_balance, unshare);
         match &opt {
             None => {
-                if let Some(left_child) = current_node.take_left() {
-                    (left_child.into(), Some(current_node.get_key_value_tuple()))
+                let mut current = into_inner(current_node, unshare);
+                if let Some(left_child) = current.take_left() {
+                    (left_child.into(), Some(current.get_key_value_tuple()))
                 } else {
-                    (None, Some(current_node.get_key_value_tuple()))
+                    (None, Some(current.get_key_value_tuple()))
                 }
             }
             Some(_) => {
-                current_node.set_right(r_child);
+                current.set_right(r_child);
                 if *need_balance {
-                    match current_node.balance() {
-                        Balanced => current_node.set_balance(LeftHeavy),
-                        RightHeavy => current_node.set_balance(Balanced),
+                    match current.balance() {
+                        Balanced => current.set_balance(LeftHeavy),
+                        RightHeavy => current.set_balance(Balanced),
                         LeftHeavy => {
-                            let left_child = current_node.take_left().unwrap();
-                            current_node = rebalance(current_node, left_child, Left);
+                            let left_child = current.take_left().unwrap();
+                            current_node = rebalance(current_node, left_child, Left, unshare);
+                            *need_balance = *current_node.balance() == Balanced;
+                            return (current_node.into(), opt);
                         }
                     }
-                    *need_balance = *current_node.balance() == Balanced;
+                    *need_balance = *current.balance() == Balanced;
                 }
-                update_count(&mut current_node);
+                update_count(current);
                 (current_node.into(), opt)
             }
         }
@@ -304,170 +717,563 @@ fn find_predecessor<K: Ord, V>(
     }
 }
 
-fn insert<K: Ord, V>(
-    mut node: Box<Node<K, V>>,
+// `cmp` places the new key relative to a node; `Equal` replaces its value.
+fn insert<K: Ord, V, A: Augment<K, V>, F>(
+    mut node: Arc<Node<K, V, A>>,
     key: K,
     value: V,
+    cmp: &mut F,
     need_balance: &mut bool,
-) -> (Option<V>, Box<Node<K, V>>) {
-    match &key.cmp(node.get_key()) {
+    unshare: Unshare<K, V, A>,
+) -> (Option<V>, Arc<Node<K, V, A>>)
+    where
+        F: FnMut(&K, &Node<K, V, A>) -> Ordering,
+{
+    let current = make_mut(&mut node, unshare);
+    match cmp(&key, current) {
         Equal => {
             *need_balance = false;
-            (Some(node.replace_value(value)), node)
+            let old_value = current.replace_value(value);
+            update_count(current);
+            (Some(old_value), node)
         }
         Less => {
-            let (opt_v, mut l_child);
-            if let Some(left_child) = node.take_left() {
-                (opt_v, l_child) = insert(left_child, key, value, need_balance);
+            let (opt_v, l_child);
+            if let Some(left_child) = current.take_left() {
+                (opt_v, l_child) = insert(left_child, key, value, cmp, need_balance, unshare);
             } else {
                 (opt_v, l_child) = (None, Node::new(key, value).into());
             };
 
-            node = if *need_balance {
-                l_child = rebalance(node, l_child, Left);
-                *need_balance = *l_child.balance() != Balanced;
-                l_child
+            if *need_balance {
+                node = rebalance(node, l_child, Left, unshare);
+                *need_balance = *node.balance() != Balanced;
             } else {
-                node.set_left(l_child.into());
-                node
+                current.set_left(l_child.into());
+                update_count(current);
             };
-            update_count(&mut node);
             (opt_v, node)
         }
         Greater => {
-            let (opt_v, mut r_child);
-            if let Some(right_child) = node.take_right() {
-                (opt_v, r_child) = insert(right_child, key, value, need_balance);
+            let (opt_v, r_child);
+            if let Some(right_child) = current.take_right() {
+                (opt_v, r_child) = insert(right_child, key, value, cmp, need_balance, unshare);
             } else {
                 (opt_v, r_child) = (None, Node::new(key, value).into());
             };
 
-            node = if *need_balance {
-                r_child = rebalance(node, r_child, Right);
-                *need_balance = *r_child.balance() != Balanced;
-                r_child
+            if *need_balance {
+                node = rebalance(node, r_child, Right, unshare);
+                *need_balance = *node.balance() != Balanced;
             } else {
-                node.set_right(r_child.into());
-                node
+                current.set_right(r_child.into());
+                update_count(current);
             };
-            update_count(&mut node);
             (opt_v, node)
         }
     }
 }
 
-fn rebalance<K: Ord, V>(
-    mut node_a: Box<Node<K, V>>,
-    mut node_b: Box<Node<K, V>>,
+// The key is known to be present. Summaries are recomputed on the way back up
+// since the new value may change them.
+fn update<K: Ord, V, A: Augment<K, V>, C, F, R>(
+    node: &mut Link<K, V, A>,
+    cmp: &mut C,
+    f: F,
+    unshare: Unshare<K, V, A>,
+) -> R
+    where
+        C: FnMut(&Node<K, V, A>) -> Ordering,
+        F: FnOnce(&mut V) -> R,
+{
+    // Changes the path in place, so the tree stays whole if `f` panics, and
+    // its summaries are brought up to date either way.
+    let current = Recount(make_mut(node.as_mut().unwrap(), unshare));
+    match cmp(current.0) {
+        Less => update(current.0.get_left_mut(), cmp, f, unshare),
+        Greater => update(current.0.get_right_mut(), cmp, f, unshare),
+        Equal => f(current.0.get_value_mut().unwrap()),
+    }
+}
+
+// Updates the counts and summary of a node once dropped, also while a panic
+// below it unwinds.
+struct Recount<'a, K: Ord, V, A: Augment<K, V>>(&'a mut Node<K, V, A>);
+
+impl<K: Ord, V, A: Augment<K, V>> Drop for Recount<'_, K, V, A> {
+    fn drop(&mut self) {
+        update_count(self.0);
+    }
+}
+
+// Rank of quantile `q` among `len` elements.
+pub(crate) fn quantile_index(q: f64, len: usize) -> Option<usize> {
+    assert!((0.0..=1.0).contains(&q), "quantile (is {q}) should be within [0, 1]");
+    let last = len.checked_sub(1)?;
+    Some((q * last as f64) as usize)
+}
+
+// Steers a descent to the node of rank `index`.
+fn by_rank<K, V, A: Augment<K, V>>(index: &mut usize) -> impl FnMut(&Node<K, V, A>) -> Ordering + '_ {
+    |node: &Node<K, V, A>| match (*index).cmp(&node.left_count) {
+        Less => Less,
+        Equal => Equal,
+        Greater => {
+            *index -= node.left_count + 1;
+            Greater
+        }
+    }
+}
+
+// Drops all but the last of each run of equal keys.
+pub(crate) fn dedup_last<K: Ord, V>(entries: &mut Vec<(K, V)>) {
+    entries.dedup_by(|next, kept| {
+        let duplicate = next.0 == kept.0;
+        if duplicate {
+            swap(next, kept);
+        }
+        duplicate
+    });
+}
+
+// Builds a tree of the next `len` entries, which must come in increasing key
+// order, in O(len). The larger half goes left, so no node leans right.
+pub(crate) fn build<K: Ord, V, A: Augment<K, V>, I>(entries: &mut I, len: usize) -> Link<K, V, A>
+    where
+        I: Iterator<Item = (K, V)>,
+{
+    if len == 0 {
+        return None;
+    }
+    let left_len = len / 2;
+    let left = build(entries, left_len);
+    let (key, value) = entries.next().unwrap();
+    let right = build(entries, len - left_len - 1);
+    assemble(left, key, value, right).into()
+}
+
+// Puts a node over two subtrees made by `build` from `n / 2` and
+// `n - n / 2 - 1` entries.
+pub(crate) fn assemble<K: Ord, V, A: Augment<K, V>>(
+    left: Link<K, V, A>,
+    key: K,
+    value: V,
+    right: Link<K, V, A>,
+) -> Node<K, V, A> {
+    // A built subtree of `n` entries is as tall as `n` has bits.
+    let built_height = |node: &Link<K, V, A>| {
+        let len = node.as_ref().map_or(0, |node| node.left_count + node.right_count + 1);
+        usize::BITS - len.leading_zeros()
+    };
+    let mut node = Node::new(key, value);
+    if built_height(&left) > built_height(&right) {
+        node.set_balance(LeftHeavy);
+    }
+    node.set_left(left);
+    node.set_right(right);
+    update_count(&mut node);
+    node
+}
+
+pub(crate) fn retain<K: Ord, V, A: Augment<K, V>, F>(
+    tree: Measured<K, V, A>,
+    f: &mut F,
+    unshare: Unshare<K, V, A>,
+    salvage: &Salvage<K, V, A>,
+) -> Measured<K, V, A>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+{
+    let (Some(mut node), height) = tree else {
+        return (None, 0);
+    };
+    let current = make_mut(&mut node, unshare);
+    let left = (current.take_left(), child_height(current, height, Left));
+    let right = (current.take_right(), child_height(current, height, Right));
+    let mut held = salvage.hold();
+    held.node = Some(node);
+    held.own[1] = right;
+    held.own[0] = retain(left, f, unshare, salvage);
+    let (_, key, value, _) = make_mut(held.node.as_mut().unwrap(), unshare).parts_mut();
+    if !f(key, value) {
+        held.node = None;
+    }
+    let right = retain(take(&mut held.own[1]), f, unshare, salvage);
+    let left = take(&mut held.own[0]);
+    match held.node.take() {
+        Some(node) => {
+            let (node, height) = join(left, node, right, unshare);
+            (node.into(), height)
+        }
+        None => concat(left, right, unshare),
+    }
+}
+
+// Splits `right` around the root of `left`, then merges the halves on each
+// side and joins the results back around that root.
+pub(crate) fn union<K: Ord, V, A: Augment<K, V>, F>(
+    left: Measured<K, V, A>,
+    right: Measured<K, V, A>,
+    f: &mut F,
+    unshare: Unshare<K, V, A>,
+    salvage: &Salvage<K, V, A>,
+) -> Measured<K, V, A>
+    where
+        F: FnMut(&K, V, V) -> V,
+{
+    match union_step(left, right, f, unshare, salvage) {
+        Continue((node, (left, lower), (right, upper))) => {
+            let mut held = salvage.hold();
+            held.node = Some(node);
+            held.own[1] = right;
+            held.other[1] = upper;
+            held.own[0] = union(left, lower, f, unshare, salvage);
+            let right = union(take(&mut held.own[1]), take(&mut held.other[1]), f, unshare, salvage);
+            let (node, height) = join(take(&mut held.own[0]), held.node.take().unwrap(), right, unshare);
+            (node.into(), height)
+        }
+        Break(done) => done,
+    }
+}
+
+type UnionStep<K, V, A> = (
+    Arc<Node<K, V, A>>,
+    (Measured<K, V, A>, Measured<K, V, A>),
+    (Measured<K, V, A>, Measured<K, V, A>),
+);
+
+// One level of `union`: the root of `left`, merged with the entry of equal
+// key from `right` if any, and the pairs of trees still to merge below and
+// above it. Breaks with the result when either tree is empty.
+pub(crate) fn union_step<K: Ord, V, A: Augment<K, V>, F>(
+    left: Measured<K, V, A>,
+    right: Measured<K, V, A>,
+    f: &mut F,
+    unshare: Unshare<K, V, A>,
+    salvage: &Salvage<K, V, A>,
+) -> ControlFlow<Measured<K, V, A>, UnionStep<K, V, A>>
+    where
+        F: FnMut(&K, V, V) -> V,
+{
+    let (Some(mut node), height) = left else {
+        return Break(right);
+    };
+    if right.0.is_none() {
+        return Break((node.into(), height));
+    }
+    let current = make_mut(&mut node, unshare);
+    let left_child = (current.take_left(), child_height(current, height, Left));
+    let right_child = (current.take_right(), child_height(current, height, Right));
+    let key = current.get_key();
+    let mut cmp = |other: &Node<K, V, A>| key.cmp(other.get_key());
+    let (lower, middle, upper) = split(right.0, right.1, &mut cmp, unshare);
+    // The entry being merged is lost if `f` panics, its values moved into it.
+    let mut held = salvage.hold();
+    held.own = [left_child, right_child];
+    held.other = [lower, upper];
+    if let Some(middle) = middle {
+        let (_, value) = into_inner(middle, unshare).get_key_value_tuple();
+        current.update_value(|key, own| f(key, own, value));
+    }
+    let [left_child, right_child] = take(&mut held.own);
+    let [lower, upper] = take(&mut held.other);
+    Continue((node, (left_child, lower), (right_child, upper)))
+}
+
+// Height of a subtree, read off the balance factors along its taller side.
+pub(crate) fn height<K: Ord, V, A: Augment<K, V>>(mut node: &Link<K, V, A>) -> usize {
+    let mut height = 0;
+    while let Some(node_ref) = node {
+        height += 1;
+        node = match node_ref.balance() {
+            LeftHeavy => node_ref.get_left(),
+            Balanced | RightHeavy => node_ref.get_right(),
+        };
+    }
+    height
+}
+
+pub(crate) fn child_height<K: Ord, V, A: Augment<K, V>>(
+    node: &Node<K, V, A>,
+    height: usize,
+    dir: Direction,
+) -> usize {
+    match (node.balance(), dir) {
+        (LeftHeavy, Right) | (RightHeavy, Left) => height - 2,
+        (_, _) => height - 1,
+    }
+}
+
+pub(crate) type Measured<K, V, A> = (Link<K, V, A>, usize);
+pub(crate) type Pieces<K, V, A> = (Measured<K, V, A>, Option<Arc<Node<K, V, A>>>, Measured<K, V, A>);
+
+// Joins two trees around a detached `middle` node whose key lies between
+// theirs. Heights travel along with the trees so that neither a join nor a
+// split ever has to measure one.
+pub(crate) fn join<K: Ord, V, A: Augment<K, V>>(
+    left: Measured<K, V, A>,
+    mut middle: Arc<Node<K, V, A>>,
+    right: Measured<K, V, A>,
+    unshare: Unshare<K, V, A>,
+) -> (Arc<Node<K, V, A>>, usize) {
+    let (left_height, right_height) = (left.1, right.1);
+    if left_height > right_height + 1 {
+        let mut need_balance = false;
+        let node = join_right(left, middle, right, &mut need_balance, unshare);
+        (node, left_height + need_balance as usize)
+    } else if right_height > left_height + 1 {
+        let mut need_balance = false;
+        let node = join_left(left, middle, right, &mut need_balance, unshare);
+        (node, right_height + need_balance as usize)
+    } else {
+        let current = make_mut(&mut middle, unshare);
+        current.set_left(left.0);
+        current.set_right(right.0);
+        current.set_balance(match left_height.cmp(&right_height) {
+            Greater => LeftHeavy,
+            Equal => Balanced,
+            Less => RightHeavy,
+        });
+        update_count(current);
+        (middle, left_height.max(right_height) + 1)
+    }
+}
+
+// Walks down the right spine of the taller left tree to a subtree at most one
+// level above `right` and puts the join there. That subtree grows by exactly
+// one level, so the way back up is the same as after an insert.
+fn join_right<K: Ord, V, A: Augment<K, V>>(
+    left: Measured<K, V, A>,
+    middle: Arc<Node<K, V, A>>,
+    right: Measured<K, V, A>,
+    need_balance: &mut bool,
+    unshare: Unshare<K, V, A>,
+) -> Arc<Node<K, V, A>> {
+    if left.1 <= right.1 + 1 {
+        *need_balance = true;
+        return join(left, middle, right, unshare).0;
+    }
+    let mut node = left.0.unwrap();
+    let current = make_mut(&mut node, unshare);
+    let height = child_height(current, left.1, Right);
+    let r_child = join_right((current.take_right(), height), middle, right, need_balance, unshare);
+    if *need_balance {
+        node = rebalance(node, r_child, Right, unshare);
+        *need_balance = *node.balance() != Balanced;
+    } else {
+        current.set_right(r_child.into());
+        update_count(current);
+    }
+    node
+}
+
+fn join_left<K: Ord, V, A: Augment<K, V>>(
+    left: Measured<K, V, A>,
+    middle: Arc<Node<K, V, A>>,
+    right: Measured<K, V, A>,
+    need_balance: &mut bool,
+    unshare: Unshare<K, V, A>,
+) -> Arc<Node<K, V, A>> {
+    if right.1 <= left.1 + 1 {
+        *need_balance = true;
+        return join(left, middle, right, unshare).0;
+    }
+    let mut node = right.0.unwrap();
+    let current = make_mut(&mut node, unshare);
+    let height = child_height(current, right.1, Left);
+    let l_child = join_left(left, middle, (current.take_left(), height), need_balance, unshare);
+    if *need_balance {
+        node = rebalance(node, l_child, Left, unshare);
+        *need_balance = *node.balance() != Balanced;
+    } else {
+        current.set_left(l_child.into());
+        update_count(current);
+    }
+    node
+}
+
+// Joins two trees whose keys are already in order, around the first entry of
+// the right one.
+pub(crate) fn concat<K: Ord, V, A: Augment<K, V>>(
+    left: Measured<K, V, A>,
+    right: Measured<K, V, A>,
+    unshare: Unshare<K, V, A>,
+) -> Measured<K, V, A> {
+    if right.0.is_none() {
+        return left;
+    }
+    let mut cmp = |node: &Node<K, V, A>| if node.left_count == 0 { Equal } else { Less };
+    let (_, first, rest) = split(right.0, right.1, &mut cmp, unshare);
+    let (node, height) = join(left, first.unwrap(), rest, unshare);
+    (node.into(), height)
+}
+
+// Cuts a tree along the path `cmp` leads down. Nodes on its `Less` side end
+// up right of the cut. A node `cmp` calls `Equal` is handed back detached.
+pub(crate) fn split<K: Ord, V, A: Augment<K, V>, F>(
+    node: Link<K, V, A>,
+    height: usize,
+    cmp: &mut F,
+    unshare: Unshare<K, V, A>,
+) -> Pieces<K, V, A>
+    where
+        F: FnMut(&Node<K, V, A>) -> Ordering,
+{
+    let Some(mut node) = node else {
+        return ((None, 0), None, (None, 0));
+    };
+    let current = make_mut(&mut node, unshare);
+    let ordering = cmp(current);
+    let left = (current.take_left(), child_height(current, height, Left));
+    let right = (current.take_right(), child_height(current, height, Right));
+    match ordering {
+        Equal => (left, Some(node), right),
+        Less => {
+            let (l, middle, r) = split(left.0, left.1, cmp, unshare);
+            let (joined, height) = join(r, node, right, unshare);
+            (l, middle, (joined.into(), height))
+        }
+        Greater => {
+            let (l, middle, r) = split(right.0, right.1, cmp, unshare);
+            let (joined, height) = join(left, node, l, unshare);
+            ((joined.into(), height), middle, r)
+        }
+    }
+}
+
+fn rebalance<K: Ord, V, A: Augment<K, V>>(
+    mut node_a: Arc<Node<K, V, A>>,
+    mut node_b: Arc<Node<K, V, A>>,
     dir: Direction,
-) -> Box<Node<K, V>> {
-    match (node_a.balance(), &dir) {
+    unshare: Unshare<K, V, A>,
+) -> Arc<Node<K, V, A>> {
+    let current = make_mut(&mut node_a, unshare);
+    match (current.balance(), &dir) {
         (Balanced, _) => {
             match dir {
                 Left => {
-                    node_a.set_balance(LeftHeavy);
-                    node_a.set_left(node_b.into());
+                    current.set_balance(LeftHeavy);
+                    current.set_left(node_b.into());
                 }
                 Right => {
-                    node_a.set_balance(RightHeavy);
-                    node_a.set_right(node_b.into());
+                    current.set_balance(RightHeavy);
+                    current.set_right(node_b.into());
                 }
             };
+            update_count(current);
             node_a
         }
         (RightHeavy, Left) | (LeftHeavy, Right) => {
-            node_a.set_balance(Balanced);
+            current.set_balance(Balanced);
             match dir {
-                Left => node_a.set_left(node_b.into()),
-                Right => node_a.set_right(node_b.into()),
+                Left => current.set_left(node_b.into()),
+                Right => current.set_right(node_b.into()),
             };
+            update_count(current);
             node_a
         }
         (_, _) => {
-            if node_a.balance().as_int() + node_b.balance().as_int() == 0 {
-                do_big_rotation(node_a, node_b)
+            if current.balance().as_int() + node_b.balance().as_int() == 0 {
+                do_big_rotation(node_a, node_b, unshare)
             } else {
-                do_small_rotation(node_a, &mut node_b);
+                do_small_rotation(node_a, &mut node_b, unshare);
                 node_b
             }
         }
     }
 }
 
-fn do_big_rotation<K: Ord, V>(
-    mut node_a: Box<Node<K, V>>,
-    mut node_b: Box<Node<K, V>>,
-) -> Box<Node<K, V>> {
+fn do_big_rotation<K: Ord, V, A: Augment<K, V>>(
+    mut node_a: Arc<Node<K, V, A>>,
+    mut node_b: Arc<Node<K, V, A>>,
+    unshare: Unshare<K, V, A>,
+) -> Arc<Node<K, V, A>> {
     let (a_bal, b_bal) = (*node_a.balance(), *node_b.balance());
+    let (a, b) = (make_mut(&mut node_a, unshare), make_mut(&mut node_b, unshare));
     let mut node_c = match &b_bal {
-        LeftHeavy | Balanced => node_b.take_left().unwrap(),
-        RightHeavy => node_b.take_right().unwrap(),
+        LeftHeavy | Balanced => b.take_left().unwrap(),
+        RightHeavy => b.take_right().unwrap(),
     };
-    if *node_c.balance() == Balanced {
-        node_a.set_balance(Balanced);
-        node_b.set_balance(Balanced);
-    } else if *node_c.balance() == a_bal {
-        node_a.set_balance(*node_b.balance());
-        node_b.set_balance(Balanced);
-        node_c.set_balance(Balanced);
+    let c = make_mut(&mut node_c, unshare);
+    if *c.balance() == Balanced {
+        a.set_balance(Balanced);
+        b.set_balance(Balanced);
+    } else if *c.balance() == a_bal {
+        a.set_balance(*b.balance());
+        b.set_balance(Balanced);
+        c.set_balance(Balanced);
     } else {
-        node_b.set_balance(*node_a.balance());
-        node_a.set_balance(Balanced);
-        node_c.set_balance(Balanced);
+        b.set_balance(*a.balance());
+        a.set_balance(Balanced);
+        c.set_balance(Balanced);
     }
     match &b_bal {
-        LeftHeavy | Balanced => rotate_right(node_b, &mut node_c),
-        RightHeavy => rotate_left(node_b, &mut node_c),
+        LeftHeavy | Balanced => rotate_right(node_b, &mut node_c, unshare),
+        RightHeavy => rotate_left(node_b, &mut node_c, unshare),
     };
     match &a_bal {
-        LeftHeavy | Balanced => rotate_right(node_a, &mut node_c),
-        RightHeavy => rotate_left(node_a, &mut node_c),
+        LeftHeavy | Balanced => rotate_right(node_a, &mut node_c, unshare),
+        RightHeavy => rotate_left(node_a, &mut node_c, unshare),
     }
     node_c
 }
 
-fn do_small_rotation<K: Ord, V>(mut node_a: Box<Node<K, V>>, node_b: &mut Box<Node<K, V>>) {
+fn do_small_rotation<K: Ord, V, A: Augment<K, V>>(
+    mut node_a: Arc<Node<K, V, A>>,
+    node_b: &mut Arc<Node<K, V, A>>,
+    unshare: Unshare<K, V, A>,
+) {
     if node_a.balance() == node_b.balance() {
-        node_a.set_balance(Balanced);
+        make_mut(&mut node_a, unshare).set_balance(Balanced);
         match node_b.balance() {
-            LeftHeavy | Balanced => rotate_right(node_a, node_b),
-            RightHeavy => rotate_left(node_a, node_b),
+            LeftHeavy | Balanced => rotate_right(node_a, node_b, unshare),
+            RightHeavy => rotate_left(node_a, node_b, unshare),
         }
-        node_b.set_balance(Balanced);
+        make_mut(node_b, unshare).set_balance(Balanced);
     } else {
         match node_a.balance() {
             LeftHeavy | Balanced => {
-                node_b.set_balance(RightHeavy);
-                rotate_right(node_a, node_b);
+                make_mut(node_b, unshare).set_balance(RightHeavy);
+                rotate_right(node_a, node_b, unshare);
             }
             RightHeavy => {
-                node_b.set_balance(LeftHeavy);
-                rotate_left(node_a, node_b);
+                make_mut(node_b, unshare).set_balance(LeftHeavy);
+                rotate_left(node_a, node_b, unshare);
             }
         }
     }
 }
 
-fn rotate_right<K: Ord, V>(mut node_a: Box<Node<K, V>>, node_b: &mut Box<Node<K, V>>) {
-    node_a.set_left(node_b.take_right());
-    update_count(&mut node_a);
-    node_b.set_right(node_a.into());
-    update_count(node_b);
+fn rotate_right<K: Ord, V, A: Augment<K, V>>(
+    mut node_a: Arc<Node<K, V, A>>,
+    node_b: &mut Arc<Node<K, V, A>>,
+    unshare: Unshare<K, V, A>,
+) {
+    let (a, b) = (make_mut(&mut node_a, unshare), make_mut(node_b, unshare));
+    a.set_left(b.take_right());
+    update_count(a);
+    b.set_right(node_a.into());
+    update_count(b);
 }
 
-fn rotate_left<K: Ord, V>(mut node_a: Box<Node<K, V>>, node_b: &mut Box<Node<K, V>>) {
-    node_a.set_right(node_b.take_left());
-    update_count(&mut node_a);
-    node_b.set_left(node_a.into());
-    update_count(node_b);
+fn rotate_left<K: Ord, V, A: Augment<K, V>>(
+    mut node_a: Arc<Node<K, V, A>>,
+    node_b: &mut Arc<Node<K, V, A>>,
+    unshare: Unshare<K, V, A>,
+) {
+    let (a, b) = (make_mut(&mut node_a, unshare), make_mut(node_b, unshare));
+    a.set_right(b.take_left());
+    update_count(a);
+    b.set_left(node_a.into());
+    update_count(b);
 }
 
-fn search<'a, K: Ord, V, Q>(
-    mut node: &'a Option<Box<Node<K, V>>>,
+fn search<'a, K, V, A: Augment<K, V>, Q>(
+    mut node: &'a Link<K, V, A>,
     key: &Q,
-) -> &'a Option<Box<Node<K, V>>>
+) -> &'a Link<K, V, A>
     where
-        K: Borrow<Q>,
+        K: Ord + Borrow<Q>,
         Q: Ord + ?Sized,
 {
     loop {
@@ -483,7 +1289,100 @@ fn search<'a, K: Ord, V, Q>(
     }
 }
 
-fn update_count<K: Ord, V>(node: &mut Box<Node<K, V>>) {
+// Number of keys below `key`, counting `key` itself when `inclusive`.
+fn rank<K, V, A: Augment<K, V>, Q>(mut node: &Link<K, V, A>, key: &Q, inclusive: bool) -> usize
+    where
+        K: Ord + Borrow<Q>,
+        Q: Ord + ?Sized,
+{
+    let mut below = 0;
+    while let Some(node_ref) = node {
+        match key.cmp(node_ref.get_key_borrowed()) {
+            Less => node = node_ref.get_left(),
+            Greater => {
+                below += node_ref.left_count + 1;
+                node = node_ref.get_right();
+            }
+            Equal => return below + node_ref.left_count + inclusive as usize,
+        }
+    }
+    below
+}
+
+// The nearest key on the `side` of `key`: `Greater` walks towards the floor,
+// `Less` towards the ceiling. An equal key is its own floor and ceiling.
+fn closest<'a, K, V, A: Augment<K, V>, Q>(
+    mut node: &'a Link<K, V, A>,
+    key: &Q,
+    side: Ordering,
+) -> Option<(&'a K, &'a V)>
+    where
+        K: Ord + Borrow<Q>,
+        Q: Ord + ?Sized,
+{
+    let mut found = None;
+    while let Some(node_ref) = node {
+        match key.cmp(node_ref.get_key_borrowed()) {
+            Equal => return Some(node_ref.get_key_value()),
+            ordering => {
+                if ordering == side {
+                    found = Some(node_ref.get_key_value());
+                }
+                node = match ordering {
+                    Less => node_ref.get_left(),
+                    _ => node_ref.get_right(),
+                };
+            }
+        }
+    }
+    found
+}
+
+// `lower_in` / `upper_in` tell that every key of the subtree already satisfies
+// that side of the range, so a subtree inside both is taken from its summary.
+fn fold_range<K, V, A: Augment<K, V>, Q, R>(
+    node: &Link<K, V, A>,
+    range: &R,
+    lower_in: bool,
+    upper_in: bool,
+) -> A::Summary
+    where
+        K: Ord + Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+{
+    let Some(node_ref) = node else {
+        return A::identity();
+    };
+    if lower_in && upper_in {
+        return node_ref.summary().clone();
+    }
+    let key = node_ref.get_key_borrowed();
+    let above_lower = lower_in
+        || match range.start_bound() {
+            Bound::Included(start) => key >= start,
+            Bound::Excluded(start) => key > start,
+            Bound::Unbounded => true,
+        };
+    if !above_lower {
+        return fold_range(node_ref.get_right(), range, lower_in, upper_in);
+    }
+    let below_upper = upper_in
+        || match range.end_bound() {
+            Bound::Included(end) => key <= end,
+            Bound::Excluded(end) => key < end,
+            Bound::Unbounded => true,
+        };
+    if !below_upper {
+        return fold_range(node_ref.get_left(), range, lower_in, upper_in);
+    }
+    let (key, value) = node_ref.get_key_value();
+    let left = fold_range(node_ref.get_left(), range, lower_in, true);
+    let right = fold_range(node_ref.get_right(), range, true, upper_in);
+    A::combine(&A::combine(&left, &A::lift(key, value)), &right)
+}
+
+pub(crate) fn update_count<K: Ord, V, A: Augment<K, V>>(node: &mut Node<K, V, A>) {
     node.left_count = node
         .get_left()
         .as_ref()
@@ -491,5 +1390,13 @@ fn update_count<K: Ord, V>(node: &mut Box<Node<K, V>>) {
     node.right_count = node
         .get_right()
         .as_ref()
-        .map_or(0, |n| n.left_count + n.right_count + 1)
+        .map_or(0, |n| n.left_count + n.right_count + 1);
+    let (key, value) = node.get_key_value();
+    let summary = A::summarize(
+        node.get_left().as_ref().map(|n| n.summary()),
+        key,
+        value,
+        node.get_right().as_ref().map(|n| n.summary()),
+    );
+    node.set_summary(summary);
 }
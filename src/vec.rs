@@ -0,0 +1,197 @@
+#![forbid(unsafe_code)]
+
+use crate::iter::Iter;
+use crate::tree::{build, AVLTreeMap};
+use std::iter::FusedIterator;
+use std::ops::{Bound, Index, IndexMut, RangeBounds};
+
+// A sequence with no stored keys. Nodes are found by their rank alone, so
+// positional edits cost O(log n) instead of shifting the tail.
+pub struct AVLVec<T> {
+    map: AVLTreeMap<(), T>,
+}
+
+impl<T> Default for AVLVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> Clone for AVLVec<T> {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<T> AVLVec<T> {
+    pub fn new() -> Self {
+        Self {
+            map: AVLTreeMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.map = AVLTreeMap::new();
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.map.nth_key_value(index).map(|(_, value)| value)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.map.nth_value_mut(index)
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.get(0)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.len().checked_sub(1).and_then(|index| self.get(index))
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.map.insert_nth(self.len(), (), value);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let index = self.len().checked_sub(1)?;
+        self.map.remove_nth(index).map(|(_, value)| value)
+    }
+
+    pub fn insert(&mut self, index: usize, value: T) {
+        let len = self.len();
+        assert!(index <= len, "insertion index (is {index}) should be <= len (is {len})");
+        self.map.insert_nth(index, (), value);
+    }
+
+    pub fn remove(&mut self, index: usize) -> T {
+        let len = self.len();
+        match self.map.remove_nth(index) {
+            Some((_, value)) => value,
+            None => panic!("removal index (is {index}) should be < len (is {len})"),
+        }
+    }
+
+    // Keeps `[0, at)` and returns `[at, len)`.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        let len = self.len();
+        assert!(at <= len, "`at` split index (is {at}) should be <= len (is {len})");
+        Self {
+            map: self.map.split_off_nth(at),
+        }
+    }
+
+    // Moves every element of `other` to the end, leaving `other` empty.
+    pub fn append(&mut self, other: &mut Self) {
+        self.map.append_nth(&mut other.map);
+    }
+
+    pub fn iter(&self) -> VecIter<'_, T> {
+        VecIter {
+            iter: self.map.iter(),
+        }
+    }
+
+    // Elements at the positions in `range`, like iterating a slice of a `Vec`.
+    pub fn range<R: RangeBounds<usize>>(&self, range: R) -> VecIter<'_, T> {
+        let len = self.len();
+        let start = match range.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => end + 1,
+            Bound::Excluded(end) => *end,
+            Bound::Unbounded => len,
+        };
+        assert!(start <= end, "slice index starts at {start} but ends at {end}");
+        assert!(end <= len, "range end index {end} out of range for slice of length {len}");
+        VecIter {
+            iter: Iter::new(self.map.root(), start, end - start),
+        }
+    }
+}
+
+impl<T> Index<usize> for AVLVec<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        let len = self.len();
+        self.get(index)
+            .unwrap_or_else(|| panic!("index out of bounds: the len is {len} but the index is {index}"))
+    }
+}
+
+impl<T> IndexMut<usize> for AVLVec<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        let len = self.len();
+        self.get_mut(index)
+            .unwrap_or_else(|| panic!("index out of bounds: the len is {len} but the index is {index}"))
+    }
+}
+
+// Collects the elements and builds the tree bottom-up in one pass.
+impl<T> FromIterator<T> for AVLVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let values: Vec<T> = iter.into_iter().collect();
+        let len = values.len();
+        let mut vec = Self::new();
+        vec.map.set_tree(build(&mut values.into_iter().map(|value| ((), value)), len));
+        vec
+    }
+}
+
+impl<T> Extend<T> for AVLVec<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.append(&mut iter.into_iter().collect());
+    }
+}
+
+impl<'a, T> IntoIterator for &'a AVLVec<T> {
+    type Item = &'a T;
+    type IntoIter = VecIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct VecIter<'a, T> {
+    iter: Iter<'a, (), T>,
+}
+
+impl<'a, T> Clone for VecIter<'a, T> {
+    fn clone(&self) -> Self {
+        Self {
+            iter: self.iter.clone(),
+        }
+    }
+}
+
+impl<'a, T> Iterator for VecIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.iter.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for VecIter<'a, T> {}
+
+impl<'a, T> FusedIterator for VecIter<'a, T> {}
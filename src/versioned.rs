@@ -0,0 +1,134 @@
+#![forbid(unsafe_code)]
+
+use crate::augment::Augment;
+use crate::iter::Iter;
+use crate::tree::AVLTreeMap;
+use std::borrow::Borrow;
+use std::collections::VecDeque;
+use std::ops::RangeBounds;
+
+// A map that keeps its past versions queryable. It starts empty at version
+// 0, and each `insert`, and each `remove` that finds its key, makes the next
+// version. Versions share every node they have in common, and a change
+// copies only the path it touches, so each version costs O(log n) memory
+// until `gc_before` lets it go.
+//
+// The `_at` methods panic for versions that were never made or have been
+// collected; `at` tells which ones are kept.
+pub struct VersionedAVLMap<K: Ord, V, A: Augment<K, V> = ()> {
+    // Oldest first, ending with the current version.
+    versions: VecDeque<AVLTreeMap<K, V, A>>,
+    oldest: u64,
+}
+
+impl<K: Ord + Clone, V: Clone, A: Augment<K, V>> Default for VersionedAVLMap<K, V, A> {
+    fn default() -> Self {
+        Self {
+            versions: VecDeque::from([AVLTreeMap::with_augment()]),
+            oldest: 0,
+        }
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> VersionedAVLMap<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<K: Ord + Clone, V: Clone, A: Augment<K, V>> VersionedAVLMap<K, V, A> {
+    pub fn version(&self) -> u64 {
+        self.oldest + self.versions.len() as u64 - 1
+    }
+
+    pub fn oldest_version(&self) -> u64 {
+        self.oldest
+    }
+
+    // The current version.
+    pub fn map(&self) -> &AVLTreeMap<K, V, A> {
+        self.versions.back().unwrap()
+    }
+
+    // The map as of `version`, if it is kept.
+    pub fn at(&self, version: u64) -> Option<&AVLTreeMap<K, V, A>> {
+        let index = usize::try_from(version.checked_sub(self.oldest)?).ok()?;
+        self.versions.get(index)
+    }
+
+    fn expect_at(&self, version: u64) -> &AVLTreeMap<K, V, A> {
+        self.at(version).unwrap_or_else(|| {
+            panic!("version {version} is not kept (versions are {} to {})", self.oldest, self.version())
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.map().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map().is_empty()
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.map().get(key)
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let mut next = self.map().clone();
+        let old = next.insert(key, value);
+        self.versions.push_back(next);
+        old
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        if !self.map().contains_key(key) {
+            return None;
+        }
+        let mut next = self.map().clone();
+        let old = next.remove(key);
+        self.versions.push_back(next);
+        old
+    }
+
+    pub fn get_at<Q>(&self, version: u64, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.expect_at(version).get(key)
+    }
+
+    pub fn range_at<Q, R>(&self, version: u64, range: R) -> Iter<'_, K, V, A>
+        where
+            K: Borrow<Q>,
+            R: RangeBounds<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.expect_at(version).range(range)
+    }
+
+    pub fn len_at(&self, version: u64) -> usize {
+        self.expect_at(version).len()
+    }
+
+    pub fn nth_at(&self, version: u64, index: usize) -> Option<(&K, &V)> {
+        self.expect_at(version).nth_key_value(index)
+    }
+
+    // Forgets the versions before `version`, freeing the nodes only they
+    // used. The current version is always kept.
+    pub fn gc_before(&mut self, version: u64) {
+        let drop = version.min(self.version()).saturating_sub(self.oldest);
+        self.versions.drain(..drop as usize);
+        self.oldest += drop;
+    }
+}
@@ -0,0 +1,114 @@
+#![forbid(unsafe_code)]
+
+use crate::multi::AVLMultiSet;
+use std::borrow::Borrow;
+use std::collections::VecDeque;
+
+enum Limit {
+    Count(usize),
+    Span(u64),
+}
+
+// The most recent samples, kept both in arrival order for eviction and in a
+// multiset for order statistics, so every update and query is O(log n).
+pub struct AVLRollingWindow<K: Ord + Clone> {
+    samples: AVLMultiSet<K>,
+    arrivals: VecDeque<(u64, K)>,
+    limit: Limit,
+    now: u64,
+}
+
+impl<K: Ord + Clone> AVLRollingWindow<K> {
+    // Keeps the last `len` samples.
+    pub fn last_n(len: usize) -> Self {
+        assert!(len > 0, "a window should hold at least one sample");
+        Self::with_limit(Limit::Count(len))
+    }
+
+    // Keeps the samples pushed less than `span` time units before the latest
+    // time seen.
+    pub fn last_span(span: u64) -> Self {
+        assert!(span > 0, "a window should span at least one time unit");
+        Self::with_limit(Limit::Span(span))
+    }
+
+    fn with_limit(limit: Limit) -> Self {
+        Self {
+            samples: AVLMultiSet::new(),
+            arrivals: VecDeque::new(),
+            limit,
+            now: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.arrivals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arrivals.is_empty()
+    }
+
+    // Pushes `value` at the latest time seen.
+    pub fn push(&mut self, value: K) {
+        self.push_at(self.now, value);
+    }
+
+    pub fn push_at(&mut self, time: u64, value: K) {
+        self.advance_to(time);
+        self.samples.insert(value.clone());
+        self.arrivals.push_back((time, value));
+        self.evict();
+    }
+
+    // Moves the clock to `time` and drops the samples that fall out.
+    pub fn advance_to(&mut self, time: u64) {
+        let now = self.now;
+        assert!(time >= now, "time (is {time}) should not go back past {now}");
+        self.now = time;
+        self.evict();
+    }
+
+    pub fn quantile(&self, q: f64) -> Option<&K> {
+        self.samples.quantile(q)
+    }
+
+    pub fn median(&self) -> Option<&K> {
+        self.samples.median()
+    }
+
+    pub fn percentile_rank<Q>(&self, value: &Q) -> Option<f64>
+        where
+            K: Borrow<Q>,
+            Q: Ord + ?Sized,
+    {
+        self.samples.percentile_rank(value)
+    }
+
+    pub fn min(&self) -> Option<&K> {
+        self.samples.nth(0)
+    }
+
+    pub fn max(&self) -> Option<&K> {
+        self.samples.nth(self.len().checked_sub(1)?)
+    }
+
+    // The samples in the window, smallest first.
+    pub fn iter(&self) -> impl Iterator<Item = &K> {
+        self.samples.iter()
+    }
+
+    fn evict(&mut self) {
+        while let Some((time, _)) = self.arrivals.front() {
+            let expired = match self.limit {
+                Limit::Count(len) => self.arrivals.len() > len,
+                Limit::Span(span) => self.now - time >= span,
+            };
+            if !expired {
+                break;
+            }
+            let (_, value) = self.arrivals.pop_front().unwrap();
+            self.samples.remove_one(&value);
+        }
+    }
+}
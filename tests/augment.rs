@@ -0,0 +1,107 @@
+use avltree::{AVLTreeMap, Augment};
+use rand::{thread_rng, Rng as _};
+use std::collections::BTreeMap;
+use std::ops::Bound::{Excluded, Included, Unbounded};
+
+mod common;
+
+use common::Sum;
+
+struct MinMax;
+
+impl Augment<i32, i64> for MinMax {
+    type Summary = Option<(i64, i64)>;
+
+    fn identity() -> Self::Summary {
+        None
+    }
+
+    fn lift(_: &i32, value: &i64) -> Self::Summary {
+        Some((*value, *value))
+    }
+
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary {
+        match (left, right) {
+            (Some(l), Some(r)) => Some((l.0.min(r.0), l.1.max(r.1))),
+            (Some(l), None) => Some(*l),
+            (None, r) => *r,
+        }
+    }
+}
+
+#[test]
+fn fold_range_sum() {
+    let mut map = AVLTreeMap::<i32, i64, Sum>::with_augment();
+    for price in 1..=100 {
+        map.insert(price, price as i64 * 10);
+    }
+    assert_eq!(map.summary(), 50500);
+    assert_eq!(map.fold_range(10..20), (10..20).sum::<i64>() * 10);
+    assert_eq!(map.fold_range(10..=20), (10..=20).sum::<i64>() * 10);
+    assert_eq!(map.fold_range(..5), 100);
+    assert_eq!(map.fold_range(95..), (95..=100).sum::<i64>() * 10);
+    assert_eq!(map.fold_range(200..300), 0);
+
+    map.insert(50, 0);
+    map.remove(&51);
+    assert_eq!(map.fold_range(50..=51), 0);
+    assert_eq!(map.summary(), 50500 - 500 - 510);
+
+    assert_eq!(map.update(&10, |volume| *volume += 5), Some(()));
+    assert_eq!(map.update(&51, |volume| *volume += 5), None);
+    assert_eq!(map.get(&10), Some(&105));
+    assert_eq!(map.fold_range(..=10), (1..=10).sum::<i64>() * 10 + 5);
+}
+
+#[test]
+fn fold_range_random() {
+    let mut rng = thread_rng();
+    let mut sums = AVLTreeMap::<i32, i64, Sum>::with_augment();
+    let mut extremes = AVLTreeMap::<i32, i64, MinMax>::with_augment();
+    let mut reference = BTreeMap::new();
+    for _ in 0..5000 {
+        let key = rng.gen_range(0..300);
+        if rng.gen_range(0..3) == 0 {
+            assert_eq!(sums.remove(&key), reference.remove(&key));
+            extremes.remove(&key);
+        } else {
+            let value = rng.gen_range(-1000..1000);
+            assert_eq!(sums.insert(key, value), reference.insert(key, value));
+            extremes.insert(key, value);
+        }
+
+        let (a, b) = (rng.gen_range(-10..310), rng.gen_range(-10..310));
+        let bounds = match rng.gen_range(0..4) {
+            0 => (Included(a), Excluded(b)),
+            1 => (Excluded(a), Included(b)),
+            2 => (Unbounded, Included(b)),
+            _ => (Included(a), Unbounded),
+        };
+        if a > b && !matches!(bounds, (Unbounded, _) | (_, Unbounded)) {
+            continue;
+        }
+        let values: Vec<i64> = reference.range(bounds).map(|(_, v)| *v).collect();
+        assert_eq!(sums.fold_range(bounds), values.iter().sum::<i64>());
+        let expected = values
+            .iter()
+            .fold(None, |acc: Option<(i64, i64)>, v| {
+                Some(acc.map_or((*v, *v), |(lo, hi)| (lo.min(*v), hi.max(*v))))
+            });
+        assert_eq!(extremes.fold_range(bounds), expected);
+    }
+}
+
+#[test]
+fn augment_survives_clone() {
+    let mut map = AVLTreeMap::<i32, i64, Sum>::with_augment();
+    for key in 0..1000 {
+        map.insert(key, 1);
+    }
+    let snapshot = map.clone();
+    for key in 0..500 {
+        map.remove(&key);
+    }
+    assert_eq!(map.summary(), 500);
+    assert_eq!(snapshot.summary(), 1000);
+    assert_eq!(snapshot.fold_range(250..750), 500);
+}
@@ -0,0 +1,21 @@
+use avltree::Augment;
+use std::ops::Add;
+
+// The sum of the values, shared by the tests of augmented maps.
+pub struct Sum;
+
+impl<K, V: Copy + Default + Add<Output = V>> Augment<K, V> for Sum {
+    type Summary = V;
+
+    fn identity() -> V {
+        V::default()
+    }
+
+    fn lift(_: &K, value: &V) -> V {
+        *value
+    }
+
+    fn combine(left: &V, right: &V) -> V {
+        *left + *right
+    }
+}
@@ -0,0 +1,95 @@
+use avltree::{AVLTreeMap, ConcurrentAVLMap};
+use rand::{rngs::StdRng, Rng as _, SeedableRng as _};
+use std::collections::BTreeMap;
+use std::thread;
+
+#[test]
+fn split_off_and_append() {
+    let mut map: AVLTreeMap<i32, i32> = (0..100).map(|key| (key, key)).collect();
+    let upper = map.split_off(&60);
+    assert!(map.iter().map(|(key, _)| *key).eq(0..60));
+    assert!(upper.iter().map(|(key, _)| *key).eq(60..100));
+
+    let mut overlapping: AVLTreeMap<i32, i32> = (50..70).map(|key| (key, -key)).collect();
+    map.append(&mut overlapping);
+    assert!(overlapping.is_empty());
+    assert_eq!(map.len(), 70);
+    assert_eq!(map.get(&55), Some(&-55));
+    let mut upper = upper;
+    map.append(&mut upper);
+    assert_eq!(map.len(), 100);
+    assert_eq!(map.get(&65), Some(&65));
+    assert_eq!(map.nth_key_value(99), Some((&99, &99)));
+}
+
+#[test]
+fn writers_on_many_threads() {
+    let map = ConcurrentAVLMap::with_max_shard_len(64);
+    thread::scope(|scope| {
+        for writer in 0..32u32 {
+            let map = &map;
+            scope.spawn(move || {
+                for index in 0..500 {
+                    map.insert(index * 32 + writer, writer);
+                }
+                for index in (0..500).step_by(2) {
+                    assert_eq!(map.remove(&(index * 32 + writer)), Some(writer));
+                }
+            });
+        }
+    });
+    assert_eq!(map.len(), 32 * 250);
+    assert!(map.shard_count() > 32);
+    let snapshot = map.snapshot();
+    let keys: Vec<u32> = snapshot.iter().map(|(key, _)| *key).collect();
+    let expected: Vec<u32> = (0..16_000).filter(|key| (key / 32) % 2 == 1).collect();
+    assert_eq!(keys, expected);
+    for (rank, key) in expected.iter().enumerate().step_by(97) {
+        assert_eq!(map.rank(key), rank);
+        assert_eq!(map.nth(rank), Some((*key, key % 32)));
+    }
+    assert_eq!(map.nth(expected.len()), None);
+
+    // Removing most keys merges shards back.
+    for key in &expected[100..] {
+        map.remove(key);
+    }
+    assert_eq!(map.len(), 100);
+    assert!(map.shard_count() <= 4);
+    assert!(map.snapshot().iter().map(|(key, _)| *key).eq(expected[..100].iter().copied()));
+}
+
+#[test]
+fn snapshots_do_not_change() {
+    let mut rng = StdRng::seed_from_u64(17);
+    let map = ConcurrentAVLMap::with_max_shard_len(16);
+    let mut expected = BTreeMap::new();
+    for _ in 0..2000 {
+        let key = rng.gen_range(0..1000);
+        map.insert(key, key * 2);
+        expected.insert(key, key * 2);
+    }
+    let snapshot = map.range_snapshot(100..=400);
+    // The writers touch different keys, so the result does not depend on
+    // how they interleave.
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            for key in 0..1000 {
+                map.insert(key, 0);
+            }
+        });
+        scope.spawn(|| {
+            for key in 1000..2000 {
+                map.insert(key, key);
+            }
+            for key in (1000..2000).step_by(3) {
+                map.remove(&key);
+            }
+        });
+    });
+    assert!(snapshot.iter().eq(expected.range(100..=400)));
+    assert_eq!(map.get(&2), Some(0));
+    assert!(!map.contains_key(&1003));
+    assert_eq!(map.len(), 2000 - 334);
+    assert_eq!(map.range_snapshot(1990..).iter().count(), 6);
+}
@@ -0,0 +1,153 @@
+use avltree::{DurableAVLMap, DurableError, SyncPolicy};
+use std::fs::{self, OpenOptions};
+use std::io::Write as _;
+use tempfile::tempdir;
+
+type Map = DurableAVLMap<u32, String>;
+
+fn contents(map: &Map) -> Vec<(u32, String)> {
+    map.map().iter().map(|(key, value)| (*key, value.clone())).collect()
+}
+
+#[test]
+fn survives_reopen() {
+    let dir = tempdir().unwrap();
+    let mut map = Map::open(dir.path(), SyncPolicy::Always).unwrap();
+    for key in 0..100 {
+        assert_eq!(map.insert(key, key.to_string()).unwrap(), None);
+    }
+    assert_eq!(map.insert(5, "five".into()).unwrap(), Some("5".into()));
+    assert_eq!(map.remove(&7).unwrap(), Some("7".into()));
+    assert_eq!(map.remove(&7).unwrap(), None);
+    assert_eq!(map.log_records(), 102);
+    let expected = contents(&map);
+    drop(map);
+
+    let map = Map::open(dir.path(), SyncPolicy::Every(10)).unwrap();
+    assert_eq!(contents(&map), expected);
+    assert_eq!(map.get(&5).map(String::as_str), Some("five"));
+    assert!(!map.contains_key(&7));
+    assert_eq!(map.len(), 99);
+}
+
+#[test]
+fn torn_tail_is_cut_off() {
+    let dir = tempdir().unwrap();
+    let mut map = Map::open(dir.path(), SyncPolicy::Never).unwrap();
+    for key in 0..10 {
+        map.insert(key, "x".repeat(key as usize)).unwrap();
+    }
+    map.sync().unwrap();
+    let expected = contents(&map);
+    drop(map);
+
+    let log = dir.path().join("log");
+    let good = fs::read(&log).unwrap();
+    let other = tempdir().unwrap();
+    let mut map = Map::open(other.path(), SyncPolicy::Always).unwrap();
+    map.insert(99, "tail".into()).unwrap();
+    let mut record = fs::read(other.path().join("log")).unwrap();
+    // Every prefix of one more record, as a crash during the append leaves
+    // it, and the whole record with a damaged body.
+    let mut tails: Vec<_> = (1..record.len()).map(|cut| record[..cut].to_vec()).collect();
+    *record.last_mut().unwrap() ^= 1;
+    tails.push(record);
+    for tail in tails {
+        fs::write(&log, [good.as_slice(), &tail].concat()).unwrap();
+        let mut map = Map::open(dir.path(), SyncPolicy::Always).unwrap();
+        assert_eq!(contents(&map), expected);
+        assert_eq!(fs::read(&log).unwrap(), good);
+        map.insert(50, "after".into()).unwrap();
+        drop(map);
+        assert_eq!(Map::open(dir.path(), SyncPolicy::Always).unwrap().len(), 11);
+        fs::write(&log, &good).unwrap();
+    }
+}
+
+#[test]
+fn zero_filled_tail_is_cut_off() {
+    let dir = tempdir().unwrap();
+    let mut map = Map::open(dir.path(), SyncPolicy::Always).unwrap();
+    for key in 0..3 {
+        map.insert(key, key.to_string()).unwrap();
+    }
+    let expected = contents(&map);
+    drop(map);
+    let log = dir.path().join("log");
+    let good = fs::read(&log).unwrap();
+    for zeros in [3, 8, 16, 4096] {
+        fs::write(&log, [good.as_slice(), &vec![0; zeros]].concat()).unwrap();
+        let map = Map::open(dir.path(), SyncPolicy::Always).unwrap();
+        assert_eq!(contents(&map), expected);
+        assert_eq!(fs::read(&log).unwrap(), good);
+    }
+}
+
+#[test]
+fn corruption_before_the_tail_is_an_error() {
+    let dir = tempdir().unwrap();
+    let mut map = Map::open(dir.path(), SyncPolicy::Always).unwrap();
+    map.insert(1, "one".into()).unwrap();
+    map.insert(2, "two".into()).unwrap();
+    drop(map);
+    let log = dir.path().join("log");
+    let mut bytes = fs::read(&log).unwrap();
+    bytes[10] ^= 0xFF;
+    fs::write(&log, &bytes).unwrap();
+    let error = Map::open(dir.path(), SyncPolicy::Always).err().unwrap();
+    assert!(matches!(error, DurableError::CorruptLog { offset: 0 }));
+    assert_eq!(error.to_string(), "log record at byte 0 is corrupt");
+}
+
+#[test]
+fn damaged_length_is_an_error() {
+    let dir = tempdir().unwrap();
+    let mut map = Map::open(dir.path(), SyncPolicy::Always).unwrap();
+    for key in 0..5 {
+        map.insert(key, format!("v{key}")).unwrap();
+    }
+    drop(map);
+    let log = dir.path().join("log");
+    let good = fs::read(&log).unwrap();
+    let record = good.len() / 5;
+    // Lengths reaching past the end of the log, from the first and from a
+    // middle record, which a torn tail would look like but for the whole
+    // records after them.
+    for start in [0, 2 * record] {
+        let mut bytes = good.clone();
+        bytes[start] = 0xF0;
+        fs::write(&log, &bytes).unwrap();
+        let error = Map::open(dir.path(), SyncPolicy::Always).err().unwrap();
+        assert!(matches!(error, DurableError::CorruptLog { offset } if offset == start as u64));
+        assert_eq!(fs::read(&log).unwrap(), bytes);
+    }
+}
+
+#[test]
+fn compaction() {
+    let dir = tempdir().unwrap();
+    let mut map = Map::open(dir.path(), SyncPolicy::Every(100)).unwrap();
+    map.set_auto_compaction(Some(50));
+    for key in 0..120 {
+        map.insert(key % 40, key.to_string()).unwrap();
+    }
+    assert_eq!(map.log_records(), 20);
+    let expected = contents(&map);
+    drop(map);
+    let map = Map::open(dir.path(), SyncPolicy::Always).unwrap();
+    assert_eq!(contents(&map), expected);
+    assert_eq!(map.log_records(), 20);
+
+    // A crash after the new snapshot is in place but before the log is
+    // emptied replays the log over it to the same state.
+    let log = fs::read(dir.path().join("log")).unwrap();
+    let mut map = map;
+    map.compact().unwrap();
+    assert_eq!(fs::metadata(dir.path().join("log")).unwrap().len(), 0);
+    drop(map);
+    OpenOptions::new().append(true).open(dir.path().join("log")).unwrap().write_all(&log).unwrap();
+    fs::write(dir.path().join("snapshot.tmp"), b"half written").unwrap();
+    let map = Map::open(dir.path(), SyncPolicy::Always).unwrap();
+    assert_eq!(contents(&map), expected);
+    assert!(!dir.path().join("snapshot.tmp").exists());
+}
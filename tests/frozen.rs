@@ -0,0 +1,68 @@
+use avltree::AVLTreeMap;
+use rand::{rngs::StdRng, Rng as _, SeedableRng as _};
+use std::collections::BTreeMap;
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
+
+#[test]
+fn matches_the_tree() {
+    let mut rng = StdRng::seed_from_u64(5);
+    for len in [0, 1, 2, 3, 7, 8, 100, 1000] {
+        let mut map = AVLTreeMap::new();
+        let mut expected = BTreeMap::new();
+        while map.len() < len {
+            let (key, value) = (rng.gen_range(0..4 * len as i32), rng.gen::<u8>());
+            map.insert(key, value);
+            expected.insert(key, value);
+        }
+        let frozen = map.freeze();
+        assert_eq!(frozen.len(), len);
+        assert!(frozen.iter().eq(expected.iter()));
+        assert!(frozen.iter().rev().eq(expected.iter().rev()));
+        for key in -1..=4 * len as i32 {
+            assert_eq!(frozen.get(&key), expected.get(&key));
+            assert_eq!(frozen.rank(&key), expected.range(..key).count());
+        }
+        for index in 0..=len {
+            assert_eq!(frozen.nth_key_value(index), expected.iter().nth(index));
+        }
+        let bound = |rng: &mut StdRng| -> Bound<i32> {
+            match rng.gen_range(0..3) {
+                0 => Included(rng.gen_range(-1..=4 * len as i32)),
+                1 => Excluded(rng.gen_range(-1..=4 * len as i32)),
+                _ => Unbounded,
+            }
+        };
+        for _ in 0..100 {
+            let range = (bound(&mut rng), bound(&mut rng));
+            let valid = match range {
+                (Included(a) | Excluded(a), Included(b) | Excluded(b)) => {
+                    a < b || (a == b && matches!(range, (Included(_), Included(_))))
+                }
+                _ => true,
+            };
+            if valid {
+                assert!(frozen.range(range).eq(expected.range(range)), "{range:?}");
+            } else {
+                assert_eq!(frozen.range(range).count(), 0);
+            }
+        }
+        let thawed = frozen.thaw();
+        assert!(thawed.iter().eq(expected.iter()));
+        assert_eq!(thawed.nth_key_value(len / 2), expected.iter().nth(len / 2));
+    }
+}
+
+#[test]
+fn borrowed_keys() {
+    let mut map = AVLTreeMap::new();
+    for word in ["pear", "apple", "fig"] {
+        map.insert(word.to_string(), word.len());
+    }
+    let frozen = map.freeze();
+    assert_eq!(frozen.get("fig"), Some(&3));
+    assert!(!frozen.contains_key("kiwi"));
+    let keys: Vec<_> = frozen.range::<str, _>((Included("b"), Excluded("g"))).map(|(key, _)| key).collect();
+    assert_eq!(keys, ["fig"]);
+    // The source map is untouched.
+    assert_eq!(map.len(), 3);
+}
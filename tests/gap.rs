@@ -0,0 +1,69 @@
+use avltree::{AVLTreeMap, IdAllocator};
+use rand::{thread_rng, Rng as _};
+use std::collections::BTreeSet;
+
+#[test]
+fn gaps_between_keys() {
+    let mut map = AVLTreeMap::new();
+    for key in [1u8, 2, 3, 5, 6, 9] {
+        map.insert(key, ());
+    }
+    assert_eq!(map.first_gap_from(0), Some(0));
+    assert_eq!(map.first_gap_from(1), Some(4));
+    assert_eq!(map.first_gap_from(5), Some(7));
+    assert_eq!(map.first_gap_from(9), Some(10));
+    assert_eq!(map.count_missing(0..=9), 4);
+    assert_eq!(map.count_missing(2..6), 1);
+    assert_eq!(map.count_missing(..), 256 - 6);
+    assert_eq!(map.count_missing(7..7), 0);
+
+    map.insert(255, ());
+    assert_eq!(map.first_gap_from(255), None);
+    assert_eq!(AVLTreeMap::<i64, ()>::new().count_missing(..), 1 << 64);
+}
+
+#[test]
+fn random_against_scan() {
+    let mut rng = thread_rng();
+    let mut map = AVLTreeMap::new();
+    let mut reference = BTreeSet::new();
+    for _ in 0..5000 {
+        let key = rng.gen_range(-100..100i32);
+        if rng.gen_range(0..4) == 0 {
+            map.remove(&key);
+            reference.remove(&key);
+        } else {
+            map.insert(key, ());
+            reference.insert(key);
+        }
+        let from = rng.gen_range(-110..110);
+        let expected = (from..).find(|id| !reference.contains(id));
+        assert_eq!(map.first_gap_from(from), expected);
+        let (a, b) = (rng.gen_range(-110..110), rng.gen_range(-110..110));
+        let expected = (a..=b).filter(|id| !reference.contains(id)).count();
+        assert_eq!(map.count_missing(a..=b), expected as u128);
+    }
+}
+
+#[test]
+fn allocator_reuses_lowest_id() {
+    let mut ids = IdAllocator::<u32>::starting_at(1);
+    assert_eq!(ids.allocate(), Some(1));
+    assert_eq!(ids.allocate(), Some(2));
+    assert!(ids.reserve(3));
+    assert!(!ids.reserve(3));
+    assert_eq!(ids.allocate(), Some(4));
+    assert!(ids.release(2));
+    assert!(!ids.release(2));
+    assert_eq!(ids.allocate(), Some(2));
+    assert_eq!(ids.allocate_from(10), Some(10));
+    assert_eq!(ids.allocate(), Some(5));
+    assert_eq!(ids.count_free(1..=10), 4);
+    assert_eq!(ids.iter().collect::<Vec<_>>(), [1, 2, 3, 4, 5, 10]);
+
+    let mut bytes = IdAllocator::<u8>::new();
+    for id in 0..=255 {
+        assert_eq!(bytes.allocate(), Some(id));
+    }
+    assert_eq!(bytes.allocate(), None);
+}
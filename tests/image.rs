@@ -0,0 +1,89 @@
+use avltree::{AVLTreeMap, FixedSize, ImageError, ImageView};
+use rand::{rngs::StdRng, Rng as _, SeedableRng as _};
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Bound;
+use tempfile::tempdir;
+
+fn image<K: Ord + FixedSize, V: FixedSize>(map: &AVLTreeMap<K, V>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    map.write_image(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn queries_in_place() {
+    let mut rng = StdRng::seed_from_u64(9);
+    let mut map = AVLTreeMap::new();
+    let mut expected = BTreeMap::new();
+    for _ in 0..5000 {
+        let (key, value) = (rng.gen_range(0..20_000u64), rng.gen::<u64>());
+        map.insert(key, value);
+        expected.insert(key, value);
+    }
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("image");
+    fs::write(&path, image(&map)).unwrap();
+    let bytes = fs::read(&path).unwrap();
+    let view = ImageView::<u64, u64>::new(&bytes).unwrap();
+    assert_eq!(view.check_sorted(), Ok(()));
+    assert_eq!(view.len(), expected.len());
+    assert!(view.iter().eq(expected.iter().map(|(k, v)| (*k, *v))));
+    for key in 0..20_001 {
+        assert_eq!(view.get(&key), expected.get(&key).copied());
+        assert_eq!(view.rank_of(&key), expected.range(..key).count());
+    }
+    for index in [0, 1, 2500, expected.len() - 1, expected.len()] {
+        assert_eq!(view.nth(index), expected.iter().nth(index).map(|(k, v)| (*k, *v)));
+    }
+    for _ in 0..100 {
+        let (low, high) = (rng.gen_range(0..20_000), rng.gen_range(0..20_000));
+        let (low, high) = (low.min(high), low.max(high));
+        assert!(view.range(low..high).eq(expected.range(low..high).map(|(k, v)| (*k, *v))));
+        assert!(view.range(low..=high).rev().eq(expected.range(low..=high).rev().map(|(k, v)| (*k, *v))));
+    }
+    assert_eq!(view.range((Bound::Excluded(10), Bound::Included(10))).count(), 0);
+}
+
+#[test]
+fn sections_are_aligned() {
+    let mut map = AVLTreeMap::new();
+    for key in 0..3u8 {
+        map.insert(key, [key; 5]);
+    }
+    let bytes = image(&map);
+    // 32-byte header, 3 keys padded to 8 bytes, 3 values of 5 bytes.
+    assert_eq!(bytes.len(), 32 + 8 + 15);
+    assert_eq!(&bytes[40..45], &[0; 5]);
+    let view = ImageView::<u8, [u8; 5]>::new(&bytes).unwrap();
+    assert_eq!(view.get(&2), Some([2; 5]));
+    assert_eq!(view.get(&3), None);
+    let empty = image(&AVLTreeMap::<u8, u8>::new());
+    assert!(ImageView::<u8, u8>::new(&empty).unwrap().is_empty());
+}
+
+#[test]
+fn bad_images() {
+    let mut map = AVLTreeMap::new();
+    for key in 0..10u32 {
+        map.insert(key, key as i64);
+    }
+    let bytes = image(&map);
+    assert_eq!(
+        ImageView::<u64, i64>::new(&bytes).err(),
+        Some(ImageError::SizeMismatch { key: 4, value: 8 })
+    );
+    assert_eq!(ImageView::<u32, i64>::new(&bytes[..bytes.len() - 1]).err(), Some(ImageError::Truncated));
+    assert_eq!(ImageView::<u32, i64>::new(&bytes[..20]).err(), Some(ImageError::Truncated));
+    assert_eq!(ImageView::<u32, i64>::new(&bytes[1..]).err(), Some(ImageError::BadMagic));
+
+    // An entry count whose padded keys overflow.
+    let mut huge = image(&AVLTreeMap::<u8, u8>::new());
+    huge[16..24].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert_eq!(ImageView::<u8, u8>::new(&huge).err(), Some(ImageError::Truncated));
+
+    let mut swapped = bytes.clone();
+    swapped[32..36].copy_from_slice(&7u32.to_le_bytes());
+    let view = ImageView::<u32, i64>::new(&swapped).unwrap();
+    assert_eq!(view.check_sorted(), Err(ImageError::Unsorted { entry: 1 }));
+}
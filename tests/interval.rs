@@ -0,0 +1,72 @@
+use avltree::IntervalTreeMap;
+use rand::{thread_rng, Rng as _};
+
+#[test]
+fn overlapping_and_containing() {
+    let mut tree = IntervalTreeMap::new();
+    tree.insert(1, 5, "a");
+    tree.insert(3, 8, "b");
+    tree.insert(10, 12, "c");
+    tree.insert(3, 8, "d");
+    tree.insert(7, 9, "e");
+    assert_eq!(tree.len(), 5);
+
+    let found: Vec<_> = tree.overlapping(4, 10).map(|(_, _, v)| *v).collect();
+    assert_eq!(found, ["a", "b", "d", "e"]);
+    let found: Vec<_> = tree.containing(5).map(|(_, _, v)| *v).collect();
+    assert_eq!(found, ["b", "d"]);
+    assert_eq!(tree.containing(9).count(), 0);
+    assert_eq!(tree.containing(11).next(), Some((&10, &12, &"c")));
+
+    assert_eq!(tree.remove(&3, &8), Some("b"));
+    assert_eq!(tree.remove(&3, &8), Some("d"));
+    assert_eq!(tree.remove(&3, &8), None);
+    assert_eq!(tree.containing(5).count(), 0);
+    assert_eq!(tree.len(), 3);
+}
+
+#[test]
+fn random_against_scan() {
+    let mut rng = thread_rng();
+    let mut tree = IntervalTreeMap::new();
+    let mut intervals: Vec<(i32, i32, usize)> = Vec::new();
+    for id in 0..3000 {
+        if rng.gen_range(0..4) == 0 && !intervals.is_empty() {
+            let (start, end, _) = intervals[rng.gen_range(0..intervals.len())];
+            let position = intervals
+                .iter()
+                .position(|&(s, e, _)| (s, e) == (start, end))
+                .unwrap();
+            let removed = intervals.remove(position);
+            assert_eq!(tree.remove(&start, &end), Some(removed.2));
+        } else {
+            let start = rng.gen_range(0..1000);
+            let end = start + rng.gen_range(0..50);
+            tree.insert(start, end, id);
+            intervals.push((start, end, id));
+        }
+
+        let start = rng.gen_range(0..1000);
+        let end = start + rng.gen_range(1..100);
+        let mut expected: Vec<_> = intervals
+            .iter()
+            .filter(|&&(s, e, _)| s < end && e > start)
+            .map(|&(s, e, id)| (s, e, id))
+            .collect();
+        expected.sort_unstable();
+        let mut found: Vec<_> = tree
+            .overlapping(start, end)
+            .map(|(s, e, id)| (*s, *e, *id))
+            .collect();
+        assert!(found.windows(2).all(|w| (w[0].0, w[0].1) <= (w[1].0, w[1].1)));
+        found.sort_unstable();
+        assert_eq!(found, expected);
+
+        let point = rng.gen_range(0..1000);
+        let expected = intervals
+            .iter()
+            .filter(|&&(s, e, _)| s <= point && point < e)
+            .count();
+        assert_eq!(tree.containing(point).count(), expected);
+    }
+}
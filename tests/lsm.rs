@@ -0,0 +1,129 @@
+use avltree::{LsmError, LsmStore, SyncPolicy};
+use rand::{rngs::StdRng, Rng as _, SeedableRng as _};
+use std::collections::BTreeMap;
+use std::fs;
+use tempfile::tempdir;
+
+type Store = LsmStore<u32, String>;
+
+fn open(dir: &std::path::Path) -> Store {
+    let mut store = Store::open(dir, SyncPolicy::Never).unwrap();
+    store.set_memtable_limit(40);
+    store.set_compaction_trigger(Some(3));
+    store
+}
+
+fn check(store: &Store, expected: &BTreeMap<u32, String>, rng: &mut StdRng) {
+    for _ in 0..50 {
+        let key = rng.gen_range(0..500);
+        assert_eq!(store.get(&key).unwrap().as_ref(), expected.get(&key), "{key}");
+    }
+    let (low, high) = (rng.gen_range(0..500), rng.gen_range(0..500));
+    let range: Vec<_> = expected.range(low.min(high)..=low.max(high)).map(|(k, v)| (*k, v.clone())).collect();
+    assert_eq!(store.range(low.min(high)..=low.max(high)).unwrap(), range);
+    let all: Vec<_> = expected.iter().map(|(k, v)| (*k, v.clone())).collect();
+    assert_eq!(store.range(..).unwrap(), all);
+}
+
+#[test]
+fn matches_btree_map_across_flushes_and_reopens() {
+    let dir = tempdir().unwrap();
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut expected = BTreeMap::new();
+    let mut store = open(dir.path());
+    for round in 0..20 {
+        for _ in 0..200 {
+            let key = rng.gen_range(0..500);
+            if rng.gen_bool(0.3) {
+                store.remove(key).unwrap();
+                expected.remove(&key);
+            } else {
+                let value = format!("{key}-{round}-{}", rng.gen_range(0..1000));
+                store.insert(key, value.clone()).unwrap();
+                expected.insert(key, value);
+            }
+        }
+        check(&store, &expected, &mut rng);
+        if round % 5 == 4 {
+            drop(store);
+            store = open(dir.path());
+            check(&store, &expected, &mut rng);
+        }
+    }
+    store.wait_for_compaction().unwrap();
+    assert!(store.run_count() <= 3);
+    store.flush().unwrap();
+    store.compact().unwrap();
+    assert_eq!(store.run_count(), 1);
+    assert_eq!(store.memtable_len(), 0);
+    check(&store, &expected, &mut rng);
+}
+
+#[test]
+fn tombstones_shadow_older_runs() {
+    let dir = tempdir().unwrap();
+    let mut store = Store::open(dir.path(), SyncPolicy::Always).unwrap();
+    store.set_compaction_trigger(None);
+    store.insert(1, "one".into()).unwrap();
+    store.insert(2, "two".into()).unwrap();
+    store.flush().unwrap();
+    store.remove(1).unwrap();
+    assert_eq!(store.get(&1).unwrap(), None);
+    store.flush().unwrap();
+    assert_eq!(store.run_count(), 2);
+    assert_eq!(store.get(&1).unwrap(), None);
+    assert_eq!(store.range(..).unwrap(), [(2, "two".to_string())]);
+
+    // Unflushed changes come back from the memtable log.
+    store.insert(3, "three".into()).unwrap();
+    drop(store);
+    let mut store = Store::open(dir.path(), SyncPolicy::Always).unwrap();
+    assert_eq!(store.get(&3).unwrap().as_deref(), Some("three"));
+    store.compact().unwrap();
+    assert_eq!(store.get(&1).unwrap(), None);
+    assert_eq!(store.range(2..).unwrap(), [(2, "two".to_string()), (3, "three".to_string())]);
+}
+
+#[test]
+fn interrupted_compaction() {
+    let dir = tempdir().unwrap();
+    let mut store = Store::open(dir.path(), SyncPolicy::Never).unwrap();
+    store.set_compaction_trigger(None);
+    store.insert(1, "old".into()).unwrap();
+    store.flush().unwrap();
+    let oldest: Vec<_> = fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().path()).collect();
+    let oldest = oldest.into_iter().find(|path| path.to_str().unwrap().contains("run-")).unwrap();
+    let bytes = fs::read(&oldest).unwrap();
+    store.remove(1).unwrap();
+    store.insert(2, "new".into()).unwrap();
+    store.flush().unwrap();
+    store.compact().unwrap();
+    assert!(!oldest.exists());
+    drop(store);
+
+    // As if the compaction stopped before deleting its inputs: the old run
+    // must not bring back the removed key.
+    fs::write(&oldest, bytes).unwrap();
+    fs::write(dir.path().join("run-00000000000000000009.tmp"), b"partial").unwrap();
+    let store = Store::open(dir.path(), SyncPolicy::Never).unwrap();
+    assert_eq!(store.run_count(), 1);
+    assert_eq!(store.get(&1).unwrap(), None);
+    assert_eq!(store.range(..).unwrap(), [(2, "new".to_string())]);
+    assert!(!oldest.exists());
+    assert!(!dir.path().join("run-00000000000000000009.tmp").exists());
+}
+
+#[test]
+fn corrupt_run_is_reported() {
+    let dir = tempdir().unwrap();
+    let mut store = Store::open(dir.path(), SyncPolicy::Never).unwrap();
+    store.insert(1, "one".into()).unwrap();
+    store.flush().unwrap();
+    drop(store);
+    let path = dir.path().join("run-00000000000000000000");
+    let mut bytes = fs::read(&path).unwrap();
+    bytes[12] ^= 1;
+    fs::write(&path, bytes).unwrap();
+    let error = Store::open(dir.path(), SyncPolicy::Never).err().unwrap();
+    assert!(matches!(error, LsmError::CorruptRun { .. }));
+}
@@ -0,0 +1,100 @@
+use avltree::{AVLMultiMap, AVLMultiSet};
+use rand::{thread_rng, Rng as _};
+
+#[test]
+fn multiset_counts_repeats() {
+    let mut set = AVLMultiSet::new();
+    for value in [5, 1, 5, 3, 5, 1] {
+        set.insert(value);
+    }
+    assert_eq!(set.len(), 6);
+    assert_eq!(set.distinct_len(), 3);
+    assert_eq!(set.count(&5), 3);
+    assert_eq!(set.count(&4), 0);
+    let all: Vec<_> = (0..set.len()).map(|i| *set.nth(i).unwrap()).collect();
+    assert_eq!(all, [1, 1, 3, 5, 5, 5]);
+    assert!(set.iter().copied().eq(all.iter().copied()));
+    assert_eq!(set.nth(6), None);
+    assert_eq!(set.rank(&3), 2);
+    assert_eq!(set.rank(&4), 3);
+    assert_eq!(set.rank(&6), 6);
+
+    assert!(set.remove_one(&5));
+    assert_eq!(set.count(&5), 2);
+    assert!(set.remove_one(&3));
+    assert!(!set.contains(&3));
+    assert!(!set.remove_one(&3));
+    assert_eq!(set.remove_all(&1), 2);
+    assert_eq!(set.iter_counts().collect::<Vec<_>>(), [(&5, 2)]);
+    assert_eq!(set.len(), 2);
+}
+
+#[test]
+fn multimap_keeps_insertion_order() {
+    let mut map = AVLMultiMap::new();
+    map.insert("b", 1);
+    map.insert("a", 2);
+    map.insert("b", 3);
+    map.insert("b", 4);
+    assert_eq!(map.len(), 4);
+    assert_eq!(map.keys_len(), 2);
+    assert_eq!(map.get_all("b").collect::<Vec<_>>(), [&1, &3, &4]);
+    assert_eq!(map.first("b"), Some(&1));
+    assert_eq!(map.count("b"), 3);
+    assert_eq!(map.nth(0), Some((&"a", &2)));
+    assert_eq!(map.nth(2), Some((&"b", &3)));
+    assert_eq!(map.rank("b"), 1);
+    assert_eq!(
+        map.iter().collect::<Vec<_>>(),
+        [(&"a", &2), (&"b", &1), (&"b", &3), (&"b", &4)]
+    );
+
+    assert_eq!(map.remove_one("b"), Some(1));
+    assert_eq!(map.remove_all("b"), [3, 4]);
+    assert_eq!(map.remove_one("b"), None);
+    assert_eq!(map.remove_one("a"), Some(2));
+    assert!(map.is_empty());
+}
+
+#[test]
+fn multiset_random() {
+    let mut rng = thread_rng();
+    let mut set = AVLMultiSet::new();
+    let mut reference: Vec<u8> = Vec::new();
+    for _ in 0..5000 {
+        let value = rng.gen_range(0u8..30);
+        match rng.gen_range(0..4) {
+            0 => {
+                let position = reference.iter().position(|v| *v == value);
+                assert_eq!(set.remove_one(&value), position.is_some());
+                if let Some(position) = position {
+                    reference.remove(position);
+                }
+            }
+            _ => {
+                set.insert(value);
+                reference.push(value);
+            }
+        }
+        reference.sort_unstable();
+        assert_eq!(set.len(), reference.len());
+        let index = rng.gen_range(0..=reference.len());
+        assert_eq!(set.nth(index), reference.get(index));
+        let probe = rng.gen_range(0u8..30);
+        assert_eq!(set.rank(&probe), reference.iter().filter(|v| **v < probe).count());
+        assert_eq!(set.count(&probe), reference.iter().filter(|v| **v == probe).count());
+    }
+}
+
+#[test]
+fn multiset_quantiles() {
+    let mut set = AVLMultiSet::new();
+    for value in [1, 2, 2, 2, 3, 9, 9, 10] {
+        set.insert(value);
+    }
+    assert_eq!(set.median(), Some(&2));
+    assert_eq!(set.quantile(0.75), Some(&9));
+    assert_eq!(set.quantile(1.0), Some(&10));
+    assert_eq!(set.percentile_rank(&2), Some(0.5));
+    assert_eq!(set.percentile_rank(&4), Some(0.625));
+}
@@ -0,0 +1,68 @@
+use avltree::AVLTreeMap;
+
+fn squares(len: i32) -> AVLTreeMap<i32, i32> {
+    let mut map = AVLTreeMap::new();
+    for key in 0..len {
+        map.insert(key * 10, key * key);
+    }
+    map
+}
+
+#[test]
+fn offset_pages() {
+    let map = squares(25);
+    let page = map.page_at_offset(10, 5);
+    assert_eq!(page.offset, 10);
+    assert_eq!(page.total, 25);
+    assert_eq!(page.items, [(&100, &100), (&110, &121), (&120, &144), (&130, &169), (&140, &196)]);
+    assert_eq!(page.prev_cursor, Some(&100));
+    assert_eq!(page.next_cursor, Some(&140));
+
+    let last = map.page_at_offset(20, 10);
+    assert_eq!(last.items.len(), 5);
+    assert_eq!(last.next_cursor, None);
+    let past_end = map.page_at_offset(100, 10);
+    assert_eq!(past_end.offset, 25);
+    assert!(past_end.items.is_empty());
+    assert_eq!(map.page_at_offset(0, 3).prev_cursor, None);
+}
+
+#[test]
+fn keyset_pages_walk_both_ways() {
+    let map = squares(23);
+    let mut keys = Vec::new();
+    let mut page = map.page_at_offset(0, 5);
+    loop {
+        keys.extend(page.items.iter().map(|(key, _)| **key));
+        match page.next_cursor {
+            Some(cursor) => {
+                let next = map.page_after(cursor, 5);
+                assert_eq!(next.offset, page.offset + 5);
+                page = next;
+            }
+            None => break,
+        }
+    }
+    assert_eq!(keys, (0..23).map(|key| key * 10).collect::<Vec<_>>());
+    assert_eq!(page.offset, 20);
+
+    let mut keys = Vec::new();
+    while let Some(cursor) = page.prev_cursor {
+        page = map.page_before(cursor, 5);
+        keys.extend(page.items.iter().rev().map(|(key, _)| **key));
+    }
+    assert_eq!(page.offset, 0);
+    assert_eq!(keys, (0..20).rev().map(|key| key * 10).collect::<Vec<_>>());
+}
+
+#[test]
+fn cursors_between_keys() {
+    let map = squares(10);
+    let page = map.page_after(&15, 2);
+    assert_eq!(page.items, [(&20, &4), (&30, &9)]);
+    assert_eq!(page.offset, 2);
+    let page = map.page_before(&15, 5);
+    assert_eq!(page.items, [(&0, &0), (&10, &1)]);
+    assert_eq!(page.next_cursor, Some(&10));
+    assert_eq!(map.page_after(&1000, 5).items, []);
+}
@@ -0,0 +1,102 @@
+#![cfg(feature = "rayon")]
+
+use avltree::{AVLTreeMap, Augment};
+use rand::{rngs::StdRng, Rng as _, SeedableRng as _};
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+
+mod common;
+
+use common::Sum;
+
+fn random_entries(rng: &mut StdRng, len: usize) -> Vec<(u32, u64)> {
+    (0..len).map(|_| (rng.gen_range(0..50_000), rng.gen_range(0..1000))).collect()
+}
+
+fn assert_same<A: Augment<u32, u64>>(map: &AVLTreeMap<u32, u64, A>, expected: &BTreeMap<u32, u64>) {
+    assert_eq!(map.len(), expected.len());
+    assert!(map.iter().eq(expected.iter()));
+    for rank in [0, expected.len() / 3, expected.len().saturating_sub(1)] {
+        assert_eq!(map.nth_key_value(rank), expected.iter().nth(rank));
+    }
+}
+
+#[test]
+fn par_iter_in_order() {
+    let map: AVLTreeMap<u32, u64> = (0..20_000).map(|key| (key, key as u64)).collect();
+    assert_eq!(map.par_iter().map(|(_, value)| value).sum::<u64>(), (0..20_000).sum());
+    let keys: Vec<u32> = map.par_iter().map(|(key, _)| *key).collect();
+    assert!(keys.into_iter().eq(0..20_000));
+    assert_eq!(AVLTreeMap::<u32, u64>::new().par_iter().count(), 0);
+}
+
+#[test]
+fn par_iter_mut_leaves_clones_alone() {
+    let mut map: AVLTreeMap<u32, u64> = (0..20_000).map(|key| (key, key as u64)).collect();
+    let snapshot = map.clone();
+    map.par_iter_mut().for_each(|(key, value)| *value += *key as u64);
+    assert!(map.iter().all(|(key, value)| *value == 2 * *key as u64));
+    assert!(snapshot.iter().all(|(key, value)| *value == *key as u64));
+}
+
+#[test]
+fn collect_keeps_last_duplicate() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let entries = random_entries(&mut rng, 30_000);
+    let map: AVLTreeMap<u32, u64> = entries.clone().into_par_iter().collect();
+    let expected: BTreeMap<u32, u64> = entries.into_iter().collect();
+    assert_same(&map, &expected);
+}
+
+#[test]
+fn par_union_and_retain() {
+    let mut rng = StdRng::seed_from_u64(11);
+    for len in [10, 5_000, 40_000] {
+        let left = random_entries(&mut rng, len);
+        let right = random_entries(&mut rng, len / 2);
+        let mut map: AVLTreeMap<u32, u64, Sum> = left.iter().copied().collect();
+        let snapshot = map.clone();
+        let mut expected: BTreeMap<u32, u64> = left.iter().copied().collect();
+
+        map.par_union_with(right.iter().copied().collect(), |_, own, other| own * 2 + other);
+        for (key, value) in right.into_iter().collect::<BTreeMap<_, _>>() {
+            let own = expected.get(&key).map_or(value, |own| own * 2 + value);
+            expected.insert(key, own);
+        }
+        assert_same(&map, &expected);
+        assert_eq!(map.summary(), expected.values().sum::<u64>());
+
+        map.par_retain(|key, _| key % 4 != 1);
+        expected.retain(|key, _| key % 4 != 1);
+        assert_same(&map, &expected);
+        assert_eq!(map.summary(), expected.values().sum::<u64>());
+        assert_same(&snapshot, &left.into_iter().collect());
+    }
+}
+
+#[test]
+fn par_retain_and_union_survive_panics() {
+    let mut map: AVLTreeMap<u32, u64> = (0..30_000).map(|key| (key, key as u64)).collect();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        map.par_retain(|key, _| if *key == 15_000 { panic!() } else { key % 2 == 0 })
+    }));
+    assert!(result.is_err());
+    // Entries are kept unless `f` said otherwise, which it did for some odd
+    // keys but not for all of them, depending on the order of the threads.
+    let keys: Vec<u32> = map.iter().map(|(key, _)| *key).collect();
+    assert_eq!(keys.len(), map.len());
+    assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!((0..30_000).step_by(2).all(|key| map.contains_key(&key)));
+    assert!(map.contains_key(&15_000));
+    assert_eq!(map.nth_key_value(map.len() / 2).map(|(key, _)| *key), Some(keys[keys.len() / 2]));
+
+    let mut map: AVLTreeMap<u32, u64> = (0..30_000).step_by(2).map(|key| (key, 0)).collect();
+    let other: AVLTreeMap<u32, u64> = (0..30_000).map(|key| (key, 1)).collect();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        map.par_union_with(other, |key, own, other| if *key == 15_000 { panic!() } else { own + other })
+    }));
+    assert!(result.is_err());
+    assert_eq!(map.len(), 29_999);
+    assert!(map.iter().map(|(key, _)| *key).eq((0..30_000).filter(|key| *key != 15_000)));
+    assert_eq!(map.nth_key_value(20_000).map(|(key, _)| *key), Some(20_001));
+}
@@ -0,0 +1,69 @@
+use avltree::{AVLTreeMap, RankView};
+use std::thread;
+
+fn tripled(len: u64) -> AVLTreeMap<u64, u64> {
+    let mut map = AVLTreeMap::new();
+    for key in 0..len {
+        map.insert(key, key * 3);
+    }
+    map
+}
+
+#[test]
+fn chunks_cover_the_map() {
+    let map = tripled(103);
+    let chunks = map.chunks_by_rank(10);
+    assert_eq!(chunks.len(), 10);
+    assert!(chunks.iter().all(|chunk| chunk.len() == 10 || chunk.len() == 11));
+    let mut offset = 0;
+    for chunk in &chunks {
+        assert_eq!(chunk.offset(), offset);
+        let (first, last) = chunk.key_bounds().unwrap();
+        assert_eq!(*first, offset as u64);
+        assert_eq!(*last, (offset + chunk.len() - 1) as u64);
+        offset += chunk.len();
+    }
+    assert_eq!(offset, 103);
+    assert!(chunks.iter().flat_map(|chunk| chunk.iter()).map(|(key, _)| *key).eq(0..103));
+
+    let small = tripled(3);
+    let chunks = small.chunks_by_rank(5);
+    assert_eq!(chunks.iter().map(|chunk| chunk.len()).collect::<Vec<_>>(), [1, 1, 1, 0, 0]);
+    assert_eq!(chunks[4].key_bounds(), None);
+}
+
+#[test]
+fn chunks_on_scoped_threads() {
+    let map = tripled(100_000);
+    let sums: Vec<u64> = thread::scope(|scope| {
+        let handles: Vec<_> = map
+            .chunks_by_rank(4)
+            .into_iter()
+            .map(|chunk| scope.spawn(move || chunk.into_iter().map(|(_, value)| value).sum::<u64>()))
+            .collect();
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    });
+    assert_eq!(sums.iter().sum::<u64>(), 3 * (0..100_000).sum::<u64>());
+}
+
+fn count_split(view: RankView<'_, u64, u64>) -> usize {
+    match view.split() {
+        Some((left, right)) => {
+            let (left, right) = thread::scope(|scope| {
+                let left = scope.spawn(move || count_split(left));
+                (left.join().unwrap(), count_split(right))
+            });
+            left + right
+        }
+        None => view.iter().count(),
+    }
+}
+
+#[test]
+fn views_split_recursively() {
+    let map = tripled(50);
+    assert_eq!(count_split(map.rank_view()), 50);
+    let (left, right) = map.rank_view().split_at(20);
+    assert_eq!((left.len(), right.offset(), right.len()), (20, 20, 30));
+    assert!(map.rank_view().split_at(0).0.is_empty());
+}
@@ -0,0 +1,86 @@
+use avltree::AVLPriorityQueue;
+use rand::{thread_rng, Rng as _};
+
+#[test]
+fn fifo_ties_from_both_ends() {
+    let mut queue = AVLPriorityQueue::new();
+    queue.push(2, "a");
+    queue.push(1, "b");
+    queue.push(2, "c");
+    queue.push(1, "d");
+    assert_eq!(queue.peek_min(), Some((&1, &"b")));
+    assert_eq!(queue.peek_max(), Some((&2, &"a")));
+    assert_eq!(queue.pop_max(), Some((2, "a")));
+    assert_eq!(queue.pop_max(), Some((2, "c")));
+    assert_eq!(queue.pop_min(), Some((1, "b")));
+    assert_eq!(queue.pop_min(), Some((1, "d")));
+    assert_eq!(queue.pop_min(), None);
+    assert_eq!(queue.pop_max(), None);
+}
+
+#[test]
+fn handles_survive_rebalancing() {
+    let mut queue = AVLPriorityQueue::new();
+    let handles: Vec<_> = (0..1000).map(|i| queue.push(i, i * 10)).collect();
+    for i in (0..1000).step_by(2) {
+        assert_eq!(queue.remove(handles[i]), Some((i, i * 10)));
+    }
+    assert_eq!(queue.remove(handles[0]), None);
+    assert_eq!(queue.change_priority(handles[0], 5), None);
+
+    assert_eq!(queue.change_priority(handles[999], 0), Some(999));
+    assert_eq!(queue.peek_min(), Some((&0, &9990)));
+    assert_eq!(queue.change_priority(handles[1], 1), Some(1));
+    assert_eq!(queue.change_priority(handles[3], 1), Some(3));
+    assert_eq!(queue.pop_max(), Some((997, 9970)));
+    assert_eq!(queue.get(handles[3]), Some((&1, &30)));
+    assert_eq!(queue.pop_min(), Some((0, 9990)));
+    assert_eq!(queue.pop_min(), Some((1, 10)));
+    assert_eq!(queue.pop_min(), Some((1, 30)));
+    assert!(!queue.contains(handles[3]));
+    assert_eq!(queue.len(), 496);
+}
+
+#[test]
+fn random_against_vec() {
+    let mut rng = thread_rng();
+    let mut queue = AVLPriorityQueue::new();
+    // (priority, value, handle) in push order. Values are the push steps.
+    let mut reference = Vec::new();
+    for step in 0..5000 {
+        match rng.gen_range(0..6) {
+            0 | 1 => {
+                let priority = rng.gen_range(0..20);
+                reference.push((priority, step, queue.push(priority, step)));
+            }
+            2 => {
+                let expected = reference
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, (priority, order, _))| (*priority, *order))
+                    .map(|(index, _)| index);
+                let expected = expected.map(|index| reference.remove(index));
+                assert_eq!(queue.pop_min(), expected.map(|(p, v, _)| (p, v)));
+            }
+            3 => {
+                let max = reference.iter().map(|(priority, _, _)| *priority).max();
+                let expected = reference.iter().position(|(priority, _, _)| Some(*priority) == max);
+                let expected = expected.map(|index| reference.remove(index));
+                assert_eq!(queue.pop_max(), expected.map(|(p, v, _)| (p, v)));
+            }
+            4 if !reference.is_empty() => {
+                let index = rng.gen_range(0..reference.len());
+                let priority = rng.gen_range(0..20);
+                let old = reference[index].0;
+                reference[index].0 = priority;
+                assert_eq!(queue.change_priority(reference[index].2, priority), Some(old));
+            }
+            _ if !reference.is_empty() => {
+                let (priority, value, handle) = reference.remove(rng.gen_range(0..reference.len()));
+                assert_eq!(queue.remove(handle), Some((priority, value)));
+            }
+            _ => {}
+        }
+        assert_eq!(queue.len(), reference.len());
+    }
+}
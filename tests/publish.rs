@@ -0,0 +1,56 @@
+use avltree::{AVLTreeMap, Publisher};
+use std::thread;
+
+#[test]
+fn readers_see_whole_versions() {
+    let mut publisher = Publisher::new();
+    let readers: Vec<_> = (0..4).map(|_| publisher.reader()).collect();
+    thread::scope(|scope| {
+        for reader in &readers {
+            scope.spawn(move || {
+                let mut last_len = 0;
+                while last_len < 10_000 {
+                    let version = reader.snapshot();
+                    // Keys are inserted in order and published in batches,
+                    // so every version holds exactly the keys below its length.
+                    let len = version.len();
+                    assert!(len >= last_len && len.is_multiple_of(100));
+                    assert!(version.iter().map(|(key, _)| *key).eq(0..len as u32));
+                    if len > 0 {
+                        let middle = len as u32 / 2;
+                        assert_eq!(version.partition_point(|key| *key < middle), middle as usize);
+                        assert_eq!(version.nth_key_value(middle as usize), Some((&middle, &(middle * 2))));
+                        assert_eq!(version.range(middle..).count(), len - middle as usize);
+                    }
+                    last_len = len;
+                }
+            });
+        }
+        for key in 0..10_000u32 {
+            publisher.map_mut().insert(key, key * 2);
+            if (key + 1).is_multiple_of(100) {
+                publisher.publish();
+            }
+        }
+    });
+    assert_eq!(publisher.snapshot().len(), 10_000);
+}
+
+#[test]
+fn versions_do_not_change() {
+    let map: AVLTreeMap<i32, i32> = (0..1000).map(|key| (key, key)).collect();
+    let mut publisher = Publisher::from_map(map);
+    let reader = publisher.reader();
+    let first = reader.snapshot();
+    for key in 0..1000 {
+        publisher.map_mut().update(&key, |value| *value += 1);
+    }
+    publisher.map_mut().retain(|key, _| key % 2 == 0);
+    assert_eq!(reader.snapshot().len(), 1000);
+    publisher.publish();
+    let second = reader.snapshot();
+    assert!(first.iter().map(|(key, value)| (*key, *value)).eq((0..1000).map(|key| (key, key))));
+    assert_eq!(second.len(), 500);
+    assert_eq!(second.get(&10), Some(&11));
+    assert_eq!(publisher.map().len(), 500);
+}
@@ -0,0 +1,75 @@
+use avltree::{AVLRangeMap, AVLRangeSet};
+use rand::{thread_rng, Rng as _};
+
+#[test]
+fn coalesce_and_split() {
+    let mut set = AVLRangeSet::new();
+    set.insert_range(0..10);
+    set.insert_range(20..30);
+    set.insert_range(10..20);
+    assert_eq!(set.len(), 1);
+    assert_eq!(set.covered_len(), 30);
+
+    set.remove_range(5..25);
+    assert_eq!(set.iter().collect::<Vec<_>>(), [&0..&5, &25..&30]);
+    assert!(set.contains(&4));
+    assert!(!set.contains(&5));
+    assert!(!set.contains(&24));
+    assert!(set.contains(&25));
+    assert_eq!(set.covered_len(), 10);
+    assert_eq!(set.gaps(-5..40).collect::<Vec<_>>(), [-5..0, 5..25, 30..40]);
+    assert_eq!(set.gaps(1..4).count(), 0);
+
+    set.insert_range(3..27);
+    assert_eq!(set.get_range(&10), Some(&0..&30));
+}
+
+#[test]
+fn map_merges_equal_values_only() {
+    let mut map = AVLRangeMap::new();
+    map.insert_range(0..10, "a");
+    map.insert_range(10..20, "b");
+    map.insert_range(20..30, "a");
+    assert_eq!(map.len(), 3);
+    map.insert_range(10..20, "a");
+    assert_eq!(map.len(), 1);
+    assert_eq!(map.get_range_value(&15), Some((&0, &30, &"a")));
+
+    map.insert_range(12..14, "c");
+    assert_eq!(
+        map.iter().collect::<Vec<_>>(),
+        [(&0, &12, &"a"), (&12, &14, &"c"), (&14, &30, &"a")]
+    );
+    assert_eq!(map.get(&13), Some(&"c"));
+    assert_eq!(map.covered_len(), 30);
+}
+
+#[test]
+fn random_against_bitmap() {
+    let mut rng = thread_rng();
+    let mut map = AVLRangeMap::new();
+    let mut points = [None; 200];
+    for _ in 0..5000 {
+        let start = rng.gen_range(0..200);
+        let end = rng.gen_range(start..=200);
+        if rng.gen_range(0..3) == 0 {
+            map.remove_range(start..end);
+            points[start as usize..end as usize].fill(None);
+        } else {
+            let value = rng.gen_range(0..3);
+            map.insert_range(start..end, value);
+            points[start as usize..end as usize].fill(Some(value));
+        }
+
+        for (point, expected) in points.iter().enumerate() {
+            assert_eq!(map.get(&(point as i32)), expected.as_ref());
+        }
+        let covered = points.iter().filter(|p| p.is_some()).count() as i32;
+        assert_eq!(map.covered_len(), covered);
+        let runs = points.windows(2).filter(|w| w[0] != w[1] && w[1].is_some()).count()
+            + points[0].is_some() as usize;
+        assert_eq!(map.len(), runs);
+        let gap_len: i32 = map.gaps(0..200).map(|gap| gap.end - gap.start).sum();
+        assert_eq!(gap_len, 200 - covered);
+    }
+}
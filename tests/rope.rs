@@ -0,0 +1,89 @@
+use avltree::AVLRope;
+use rand::{thread_rng, Rng as _};
+
+fn char_to_byte(text: &str, char_idx: usize) -> usize {
+    text.char_indices().nth(char_idx).map_or(text.len(), |(offset, _)| offset)
+}
+
+fn assert_same(rope: &AVLRope, text: &str) {
+    assert_eq!(rope.to_string(), text);
+    assert_eq!(rope.len_bytes(), text.len());
+    assert_eq!(rope.len_chars(), text.chars().count());
+    assert_eq!(rope.len_lines(), text.matches('\n').count() + 1);
+    assert!(rope.chunks().all(|chunk| !chunk.is_empty()));
+}
+
+#[test]
+fn edit_and_lines() {
+    let mut rope = AVLRope::from("hello\nworld\n");
+    rope.insert_str(6, "brave new ");
+    assert_same(&rope, "hello\nbrave new world\n");
+    rope.remove(0..6);
+    assert_same(&rope, "brave new world\n");
+    rope.insert_str(0, "héllo\n");
+    assert_eq!(rope.len_lines(), 3);
+    assert_eq!(rope.line_to_char(0), 0);
+    assert_eq!(rope.line_to_char(1), 6);
+    assert_eq!(rope.line_to_char(2), 22);
+    assert_eq!(rope.line_to_char(3), 22);
+    assert_eq!(rope.char_to_line(5), 0);
+    assert_eq!(rope.char_to_line(6), 1);
+    assert_eq!(rope.char_to_line(22), 2);
+    assert_eq!(rope.char_to_byte(2), 3);
+    assert_eq!(rope.byte_to_char(3), 2);
+    assert!(rope.chars().eq("héllo\nbrave new world\n".chars()));
+}
+
+#[test]
+fn large_text_is_chunked() {
+    let line = "αβγ line of text\n";
+    let text = line.repeat(1000);
+    let mut rope = AVLRope::from(text.as_str());
+    assert_same(&rope, &text);
+    assert!(rope.chunks().len() > 1);
+    assert_eq!(rope.line_to_char(500), 500 * line.chars().count());
+    assert_eq!(rope.char_to_line(500 * line.chars().count() - 1), 499);
+
+    let snapshot = rope.clone();
+    rope.remove(10..rope.len_chars() - 10);
+    assert_eq!(rope.len_chars(), 20);
+    assert_eq!(rope.chunks().len(), 1);
+    assert_same(&snapshot, &text);
+}
+
+#[test]
+fn random_against_string() {
+    let mut rng = thread_rng();
+    let alphabet = ['a', 'b', '\n', 'é', '€', '𝄞'];
+    let mut rope = AVLRope::new();
+    let mut text = String::new();
+    for _ in 0..3000 {
+        let chars = text.chars().count();
+        if rng.gen_range(0..3) == 0 && chars > 0 {
+            let start = rng.gen_range(0..chars);
+            let span = rng.gen_range(1..800);
+            let end = rng.gen_range(start..=chars.min(start + span));
+            rope.remove(start..end);
+            text.replace_range(char_to_byte(&text, start)..char_to_byte(&text, end), "");
+        } else {
+            let len = if rng.gen_range(0..10) == 0 { 700 } else { 5 };
+            let insert: String = (0..rng.gen_range(0..len))
+                .map(|_| alphabet[rng.gen_range(0..alphabet.len())])
+                .collect();
+            let at = rng.gen_range(0..=chars);
+            rope.insert_str(at, &insert);
+            text.insert_str(char_to_byte(&text, at), &insert);
+        }
+        assert_eq!(rope.len_chars(), text.chars().count());
+
+        let char_idx = rng.gen_range(0..=rope.len_chars());
+        let byte_idx = char_to_byte(&text, char_idx);
+        let line = text[..byte_idx].matches('\n').count();
+        assert_eq!(rope.char_to_byte(char_idx), byte_idx);
+        assert_eq!(rope.byte_to_char(byte_idx), char_idx);
+        assert_eq!(rope.char_to_line(char_idx), line);
+        let line_start = text[..byte_idx].rfind('\n').map_or(0, |offset| offset + 1);
+        assert_eq!(rope.line_to_char(line), text[..line_start].chars().count());
+    }
+    assert_same(&rope, &text);
+}
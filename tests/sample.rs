@@ -0,0 +1,86 @@
+#![cfg(feature = "rand")]
+
+use avltree::{AVLTreeMap, Augment};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::HashSet;
+
+struct Weight;
+
+impl Augment<u32, u64> for Weight {
+    type Summary = u64;
+
+    fn identity() -> u64 {
+        0
+    }
+
+    fn lift(_: &u32, weight: &u64) -> u64 {
+        *weight
+    }
+
+    fn combine(left: &u64, right: &u64) -> u64 {
+        left + right
+    }
+}
+
+#[test]
+fn uniform_samples() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut map = AVLTreeMap::new();
+    assert_eq!(map.sample(&mut rng), None);
+    for key in 0..100 {
+        map.insert(key, key * 2);
+    }
+
+    let mut counts = [0; 100];
+    for _ in 0..100_000 {
+        let (key, value) = map.sample(&mut rng).unwrap();
+        assert_eq!(*value, key * 2);
+        counts[*key as usize] += 1;
+    }
+    assert!(counts.iter().all(|count| (800..1200).contains(count)));
+
+    for _ in 0..1000 {
+        let (key, _) = map.sample_in_range(10..20, &mut rng).unwrap();
+        assert!((10..20).contains(key));
+    }
+    assert_eq!(map.sample_in_range(200.., &mut rng), None);
+    assert_eq!(map.sample_in_range(50..=50, &mut rng), Some((&50, &100)));
+}
+
+#[test]
+fn samples_without_replacement() {
+    let mut rng = StdRng::seed_from_u64(11);
+    let mut map = AVLTreeMap::new();
+    for key in 0..1000 {
+        map.insert(key, ());
+    }
+    let picked = map.sample_k_without_replacement(100, &mut rng);
+    assert_eq!(picked.len(), 100);
+    assert_eq!(picked.iter().map(|(key, _)| **key).collect::<HashSet<_>>().len(), 100);
+    assert_eq!(map.sample_k_without_replacement(5000, &mut rng).len(), 1000);
+}
+
+#[test]
+fn weighted_samples() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut map = AVLTreeMap::<u32, u64, Weight>::with_augment();
+    assert_eq!(map.sample_weighted(&mut rng), None);
+    map.insert(1, 0);
+    assert_eq!(map.sample_weighted(&mut rng), None);
+    for key in 2..=10 {
+        map.insert(key, key as u64);
+    }
+
+    let mut counts = [0u64; 11];
+    let draws = 540_000;
+    for _ in 0..draws {
+        let (key, _) = map.sample_weighted(&mut rng).unwrap();
+        counts[*key as usize] += 1;
+    }
+    assert_eq!(counts[1], 0);
+    for key in 2..=10 {
+        let expected = draws * key / 54;
+        assert!(counts[key as usize].abs_diff(expected) < expected / 10);
+    }
+}
@@ -0,0 +1,87 @@
+#![cfg(feature = "serde")]
+
+use avltree::{AVLTreeMap, ShapePreserving};
+use rand::{rngs::StdRng, Rng as _, SeedableRng as _};
+use std::collections::BTreeMap;
+
+mod common;
+
+use common::Sum;
+
+// Inserted in random order, so the shape is not the one a bulk build makes.
+fn random_map(seed: u64, len: usize) -> AVLTreeMap<i32, i64, Sum> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut map = AVLTreeMap::with_augment();
+    for _ in 0..len {
+        map.insert(rng.gen_range(-10_000..10_000), rng.gen_range(-100..100));
+    }
+    for _ in 0..len / 3 {
+        map.remove(&rng.gen_range(-10_000..10_000));
+    }
+    map
+}
+
+#[test]
+fn map_round_trip() {
+    let map = random_map(1, 3000);
+    let json = serde_json::to_string(&map).unwrap();
+    let expected: BTreeMap<i32, i64> = serde_json::from_str(&json).unwrap();
+    assert!(map.iter().eq(expected.iter()));
+
+    let restored: AVLTreeMap<i32, i64, Sum> = serde_json::from_str(&json).unwrap();
+    assert!(restored.iter().eq(map.iter()));
+    assert_eq!(restored.summary(), map.summary());
+
+    let bytes = bincode::serialize(&map).unwrap();
+    let restored: AVLTreeMap<i32, i64, Sum> = bincode::deserialize(&bytes).unwrap();
+    assert!(restored.iter().eq(map.iter()));
+    assert_eq!(restored.nth_key_value(100), map.nth_key_value(100));
+}
+
+#[test]
+fn unsorted_input() {
+    let map: AVLTreeMap<i32, &str> = serde_json::from_str(r#"{"3": "c", "1": "a", "2": "b", "1": "z"}"#).unwrap();
+    assert_eq!(map.iter().collect::<Vec<_>>(), [(&1, &"z"), (&2, &"b"), (&3, &"c")]);
+    let empty: AVLTreeMap<i32, i32> = serde_json::from_str("{}").unwrap();
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn shape_round_trip() {
+    for len in [0, 1, 2, 10, 5000] {
+        let map = random_map(len as u64, len);
+        let bytes = bincode::serialize(&ShapePreserving(&map)).unwrap();
+        let ShapePreserving(restored): ShapePreserving<AVLTreeMap<i32, i64, Sum>> =
+            bincode::deserialize(&bytes).unwrap();
+        assert!(restored.iter().eq(map.iter()));
+        assert_eq!(restored.summary(), map.summary());
+        assert_eq!(bincode::serialize(&ShapePreserving(&restored)).unwrap(), bytes);
+
+        let json = serde_json::to_string(&ShapePreserving(&map)).unwrap();
+        let ShapePreserving(restored): ShapePreserving<AVLTreeMap<i32, i64, Sum>> =
+            serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&ShapePreserving(restored)).unwrap(), json);
+    }
+
+    // The plain encoding forgets the shape: a tree built from it leans left.
+    let map = random_map(7, 5000);
+    let rebuilt: AVLTreeMap<i32, i64, Sum> = bincode::deserialize(&bincode::serialize(&map).unwrap()).unwrap();
+    assert_ne!(
+        bincode::serialize(&ShapePreserving(&rebuilt)).unwrap(),
+        bincode::serialize(&ShapePreserving(&map)).unwrap()
+    );
+}
+
+#[test]
+fn shape_is_checked() {
+    type Shaped = ShapePreserving<AVLTreeMap<i32, i32>>;
+    assert!(serde_json::from_str::<Shaped>("[[1, 1, 2, 0], [0, 0, 1, 0]]").is_ok());
+    // Wrong balance factor.
+    assert!(serde_json::from_str::<Shaped>("[[1, 0, 2, 0], [0, 0, 1, 0]]").is_err());
+    // Keys out of order.
+    assert!(serde_json::from_str::<Shaped>("[[1, 1, 1, 0], [0, 0, 2, 0]]").is_err());
+    // Left subtree larger than the tree.
+    assert!(serde_json::from_str::<Shaped>("[[2, 1, 2, 0], [0, 0, 1, 0]]").is_err());
+    // A chain of three is out of balance.
+    assert!(serde_json::from_str::<Shaped>("[[2, 1, 3, 0], [1, 1, 2, 0], [0, 0, 1, 0]]").is_err());
+}
@@ -0,0 +1,115 @@
+use avltree::{AVLTreeMap, Codec, SnapshotError};
+use std::cmp::Ordering;
+
+mod common;
+
+use common::Sum;
+
+fn snapshot<K: Ord + Codec, V: Codec>(map: &AVLTreeMap<K, V>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    map.write_snapshot(&mut bytes).unwrap();
+    bytes
+}
+
+fn read_error<K: Ord + Codec, V: Codec>(bytes: &[u8]) -> SnapshotError {
+    AVLTreeMap::<K, V>::read_snapshot(bytes).err().unwrap()
+}
+
+fn numbers(len: u64) -> AVLTreeMap<u64, String> {
+    let mut map = AVLTreeMap::new();
+    for key in 0..len {
+        map.insert(key * 7, key.to_string().repeat(key as usize % 5));
+    }
+    map
+}
+
+#[test]
+fn round_trip() {
+    // Large enough to span several blocks.
+    let map = numbers(20_000);
+    let bytes = snapshot(&map);
+    let restored = AVLTreeMap::<u64, String>::read_snapshot(bytes.as_slice()).unwrap();
+    assert!(restored.iter().eq(map.iter()));
+
+    let lengths: AVLTreeMap<u64, u64> = map.iter().map(|(key, value)| (*key, value.len() as u64)).collect();
+    let summed = AVLTreeMap::<u64, u64, Sum>::read_snapshot(snapshot(&lengths).as_slice()).unwrap();
+    assert_eq!(summed.summary(), map.iter().map(|(_, value)| value.len() as u64).sum::<u64>());
+    assert_eq!(summed.nth_key_value(123), lengths.nth_key_value(123));
+
+    let empty = AVLTreeMap::<u8, ()>::new();
+    assert!(AVLTreeMap::<u8, ()>::read_snapshot(snapshot(&empty).as_slice()).unwrap().is_empty());
+}
+
+#[test]
+fn header_errors() {
+    let mut bytes = snapshot(&numbers(10));
+    bytes[7] ^= 1;
+    let error = read_error::<u64, String>(bytes.as_slice());
+    assert!(matches!(error, SnapshotError::BadMagic));
+    bytes[7] ^= 1;
+    bytes[8] = 9;
+    let error = read_error::<u64, String>(bytes.as_slice());
+    assert!(matches!(error, SnapshotError::UnsupportedVersion(9)));
+    assert_eq!(error.to_string(), "unsupported snapshot version 9");
+}
+
+#[test]
+fn corruption_never_panics() {
+    let bytes = snapshot(&numbers(30));
+    for len in 0..bytes.len() {
+        let error = read_error::<u64, String>(&bytes[..len]);
+        assert!(matches!(error, SnapshotError::Truncated), "{len}: {error}");
+    }
+    for index in 0..bytes.len() {
+        let mut flipped = bytes.clone();
+        flipped[index] ^= 0x10;
+        assert!(AVLTreeMap::<u64, String>::read_snapshot(flipped.as_slice()).is_err(), "{index}");
+    }
+    // The payload of the only block starts after the 20-byte header and the
+    // 8-byte block header. Flipping a bit in it breaks the checksum.
+    let mut flipped = bytes.clone();
+    flipped[30] ^= 1;
+    let error = read_error::<u64, String>(flipped.as_slice());
+    assert!(matches!(error, SnapshotError::Checksum { block: 0 }));
+}
+
+#[test]
+fn decoding_errors() {
+    let bytes = snapshot(&numbers(10));
+    // Keys are 8 bytes, so they do not decode as `u32`.
+    let error = read_error::<u32, String>(bytes.as_slice());
+    assert!(matches!(error, SnapshotError::BadRecord { entry: 0 }));
+
+    let mut map = AVLTreeMap::new();
+    for key in 0..10u64 {
+        map.insert(Descending(key), ());
+    }
+    let error = read_error::<u64, ()>(snapshot(&map).as_slice());
+    assert!(matches!(error, SnapshotError::Unsorted { entry: 1 }));
+}
+
+// Ordered backwards, so its snapshot is out of order for plain `u64` keys.
+#[derive(PartialEq, Eq)]
+struct Descending(u64);
+
+impl PartialOrd for Descending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Descending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.cmp(&self.0)
+    }
+}
+
+impl Codec for Descending {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        u64::decode(bytes).map(Descending)
+    }
+}
@@ -0,0 +1,79 @@
+use avltree::AVLSortedSet;
+use rand::{thread_rng, Rng as _};
+use std::collections::HashMap;
+
+#[test]
+fn leaderboard() {
+    let mut board = AVLSortedSet::new();
+    assert!(board.zadd("alice", 30));
+    assert!(board.zadd("bob", 10));
+    assert!(board.zadd("carol", 20));
+    assert!(board.zadd("dave", 20));
+    assert!(!board.zadd("bob", 40));
+    assert_eq!(board.len(), 4);
+
+    assert_eq!(board.zrank("carol"), Some(0));
+    assert_eq!(board.zrank("dave"), Some(1));
+    assert_eq!(board.zrank("bob"), Some(3));
+    assert_eq!(board.zrevrank("bob"), Some(0));
+    assert_eq!(board.zrank("erin"), None);
+
+    assert_eq!(board.zincrby("carol", 15), 35);
+    assert_eq!(board.zincrby("erin", 5), 5);
+    assert_eq!(board.zscore("carol"), Some(&35));
+
+    let top: Vec<_> = board.zrange_by_rank(3..).map(|(member, _)| *member).collect();
+    assert_eq!(top, ["carol", "bob"]);
+    let middle: Vec<_> = board.zrange_by_score(20..=35).collect();
+    assert_eq!(middle, [(&"dave", &20), (&"alice", &30), (&"carol", &35)]);
+    assert_eq!(board.zcount(..30), 2);
+    assert_eq!(board.zrange_by_rank(10..20).len(), 0);
+
+    assert!(board.zrem("alice"));
+    assert!(!board.zrem("alice"));
+    assert_eq!(board.zcount(..), 4);
+}
+
+#[test]
+fn random_against_sorting() {
+    let mut rng = thread_rng();
+    let mut board = AVLSortedSet::new();
+    let mut reference = HashMap::new();
+    for _ in 0..3000 {
+        let member = rng.gen_range(0..200);
+        match rng.gen_range(0..4) {
+            0 => assert_eq!(board.zrem(&member), reference.remove(&member).is_some()),
+            1 => {
+                let delta = rng.gen_range(-50..50);
+                let score = reference.entry(member).or_insert(0);
+                *score += delta;
+                assert_eq!(board.zincrby(member, delta), *score);
+            }
+            _ => {
+                let score = rng.gen_range(0..100);
+                assert_eq!(board.zadd(member, score), reference.insert(member, score).is_none());
+            }
+        }
+
+        let mut sorted: Vec<_> = reference.iter().map(|(member, score)| (*score, *member)).collect();
+        sorted.sort();
+        let probe = rng.gen_range(0..200);
+        let expected = sorted.iter().position(|(_, member)| *member == probe);
+        assert_eq!(board.zrank(&probe), expected);
+        assert_eq!(board.zrevrank(&probe), expected.map(|rank| sorted.len() - 1 - rank));
+
+        let (low, high) = (rng.gen_range(-60..120), rng.gen_range(-60..120));
+        let in_range: Vec<_> = sorted
+            .iter()
+            .filter(|(score, _)| (low..high).contains(score))
+            .map(|(score, member)| (*member, *score))
+            .collect();
+        assert_eq!(board.zcount(low..high), in_range.len());
+        let found: Vec<_> = board.zrange_by_score(low..high).map(|(m, s)| (*m, *s)).collect();
+        assert_eq!(found, in_range);
+
+        let start = rng.gen_range(0..=sorted.len());
+        let by_rank: Vec<_> = board.zrange_by_rank(start..=start + 5).map(|(m, s)| (*s, *m)).collect();
+        assert_eq!(by_rank, sorted[start..sorted.len().min(start + 6)]);
+    }
+}
@@ -1,8 +1,13 @@
 use ntest::{assert_false, timeout};
 use rand::{seq::SliceRandom as _, thread_rng, Rng as _};
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
 use avltree::AVLTreeMap;
 
+mod common;
+
+use common::Sum;
+
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
 struct Number(i32);
 
@@ -280,3 +285,274 @@ fn performance3() {
         assert_false!(map.contains_key(&(-count)));
     }
 }
+
+#[test]
+fn clone_shares_until_mutation() {
+    let mut map = AVLTreeMap::new();
+    for i in 0..100 {
+        map.insert(i, i);
+    }
+    let snapshot = map.clone();
+    for i in 0..50 {
+        assert_eq!(map.remove(&i), Some(i));
+    }
+    for i in 100..150 {
+        assert_eq!(map.insert(i, i), None);
+    }
+    assert_eq!(map.insert(75, 0), Some(75));
+
+    assert_eq!(snapshot.len(), 100);
+    for i in 0..100 {
+        assert_eq!(snapshot.get(&i), Some(&i));
+        assert_eq!(snapshot.nth_key_value(i as usize), Some((&i, &i)));
+    }
+    assert!(!snapshot.contains_key(&100));
+    assert_eq!(map.len(), 100);
+    assert_eq!(map.get(&75), Some(&0));
+    assert_eq!(map.nth_key_value(0), Some((&50, &50)));
+}
+
+#[test]
+fn remove_missing_key_keeps_clone_shared() {
+    static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+    struct Counted(i32);
+
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            CLONES.fetch_add(1, Relaxed);
+            Counted(self.0)
+        }
+    }
+
+    let mut map: AVLTreeMap<i32, Counted> = (0..100).map(|i| (i * 2, Counted(i))).collect();
+    let snapshot = map.clone();
+    for i in 0..100 {
+        assert!(map.remove(&(i * 2 + 1)).is_none());
+    }
+    assert_eq!(CLONES.load(Relaxed), 0);
+    assert_eq!(map.remove(&2).map(|value| value.0), Some(1));
+    assert!(CLONES.load(Relaxed) > 0);
+    assert_eq!(snapshot.len(), 100);
+}
+
+#[test]
+fn clone_random() {
+    let mut rng = thread_rng();
+    let mut maps = vec![(AVLTreeMap::new(), HashMap::new())];
+    for _ in 0..20000 {
+        let index = rng.gen_range(0..maps.len());
+        let (map, hash_map) = &mut maps[index];
+        let key = rng.gen_range(0u8..64);
+        match rng.gen_range(0usize..10) {
+            0..=5 => assert_eq!(map.insert(key, key), hash_map.insert(key, key)),
+            6..=8 => assert_eq!(map.remove_entry(&key), hash_map.remove_entry(&key)),
+            9 => {
+                let copy = (map.clone(), hash_map.clone());
+                maps.push(copy);
+            }
+            _ => unreachable!(),
+        }
+    }
+    for (map, hash_map) in &maps {
+        let mut sorted: Vec<_> = hash_map.iter().collect();
+        sorted.sort_unstable();
+        assert_eq!(map.len(), sorted.len());
+        for (i, (key, value)) in sorted.into_iter().enumerate() {
+            assert_eq!(map.nth_key_value(i), Some((key, value)));
+        }
+    }
+}
+
+#[test]
+fn clone_to_thread() {
+    let mut map = AVLTreeMap::new();
+    for i in 0..10000u64 {
+        map.insert(i, i);
+    }
+    let snapshot = map.clone();
+    let reader = std::thread::spawn(move || {
+        (0..snapshot.len())
+            .map(|i| *snapshot.nth_key_value(i).unwrap().1)
+            .sum::<u64>()
+    });
+    for i in 0..10000u64 {
+        map.insert(i, 0);
+    }
+    assert_eq!(reader.join().unwrap(), 10000 * 9999 / 2);
+    assert_eq!(map.get(&9999), Some(&0));
+}
+
+#[test]
+fn floor_ceiling() {
+    let mut map = AVLTreeMap::new();
+    for key in (10..100).step_by(10) {
+        map.insert(key, key);
+    }
+    assert_eq!(map.floor_key_value(&5), None);
+    assert_eq!(map.floor_key_value(&10), Some((&10, &10)));
+    assert_eq!(map.floor_key_value(&55), Some((&50, &50)));
+    assert_eq!(map.floor_key_value(&1000), Some((&90, &90)));
+    assert_eq!(map.ceiling_key_value(&5), Some((&10, &10)));
+    assert_eq!(map.ceiling_key_value(&55), Some((&60, &60)));
+    assert_eq!(map.ceiling_key_value(&90), Some((&90, &90)));
+    assert_eq!(map.ceiling_key_value(&91), None);
+}
+
+#[test]
+fn iter_and_range() {
+    let mut rng = thread_rng();
+    let mut map = AVLTreeMap::new();
+    let mut keys = HashSet::new();
+    for _ in 0..2000 {
+        let key = rng.gen_range(0..5000);
+        map.insert(key, key * 2);
+        keys.insert(key);
+    }
+    let mut sorted: Vec<i32> = keys.into_iter().collect();
+    sorted.sort_unstable();
+    assert_eq!(map.iter().len(), sorted.len());
+    assert!(map.iter().map(|(k, _)| *k).eq(sorted.iter().copied()));
+    assert!((&map).into_iter().all(|(k, v)| *v == k * 2));
+
+    for _ in 0..200 {
+        let (a, b) = (rng.gen_range(-10..5010), rng.gen_range(-10..5010));
+        let expected: Vec<i32> = sorted.iter().copied().filter(|k| a <= *k && *k < b).collect();
+        assert!(map.range(a..b).map(|(k, _)| *k).eq(expected.iter().copied()));
+        assert_eq!(map.range(a..b).len(), expected.len());
+        let expected = sorted.iter().filter(|k| a < **k && **k <= b).count();
+        assert_eq!(
+            map.range((std::ops::Bound::Excluded(a), std::ops::Bound::Included(b))).count(),
+            expected
+        );
+        assert_eq!(map.range(..a).count(), sorted.iter().filter(|k| **k < a).count());
+        assert_eq!(map.range(a..).count(), sorted.iter().filter(|k| **k >= a).count());
+    }
+}
+
+#[test]
+fn quantiles() {
+    let mut map = AVLTreeMap::new();
+    assert_eq!(map.median(), None);
+    assert_eq!(map.percentile_rank(&0), None);
+    for key in 1..=100 {
+        map.insert(key, ());
+    }
+    assert_eq!(map.quantile(0.0), Some((&1, &())));
+    assert_eq!(map.quantile(1.0), Some((&100, &())));
+    assert_eq!(map.quantile(0.99), Some((&99, &())));
+    assert_eq!(map.median(), Some((&50, &())));
+    assert_eq!(map.percentile_rank(&25), Some(0.25));
+    assert_eq!(map.percentile_rank(&0), Some(0.0));
+    assert_eq!(map.percentile_rank(&1000), Some(1.0));
+}
+
+#[test]
+#[should_panic(expected = "quantile (is 1.5) should be within [0, 1]")]
+fn quantile_out_of_range() {
+    let mut map = AVLTreeMap::new();
+    map.insert(1, 1);
+    map.quantile(1.5);
+}
+
+#[test]
+fn collect_retain_union() {
+    let mut rng = thread_rng();
+    for _ in 0..50 {
+        let left: Vec<(u16, u32)> = (0..rng.gen_range(0..500)).map(|i| (rng.gen_range(0..400), i)).collect();
+        let right: Vec<(u16, u32)> = (0..rng.gen_range(0..500)).map(|i| (rng.gen_range(0..400), i)).collect();
+        let mut map: AVLTreeMap<_, _> = left.iter().copied().collect();
+        let mut expected: std::collections::BTreeMap<_, _> = left.iter().copied().collect();
+        assert!(map.iter().map(|(k, v)| (*k, *v)).eq(expected.iter().map(|(k, v)| (*k, *v))));
+
+        let snapshot = map.clone();
+        let other: AVLTreeMap<_, _> = right.iter().copied().collect();
+        map.union_with(other, |_, own, other| own.wrapping_mul(31).wrapping_add(other));
+        for (key, value) in right.iter().copied().collect::<std::collections::BTreeMap<_, _>>() {
+            let merged = match expected.get(&key) {
+                Some(own) => own.wrapping_mul(31).wrapping_add(value),
+                None => value,
+            };
+            expected.insert(key, merged);
+        }
+        assert_eq!(map.len(), expected.len());
+        assert!(map.iter().map(|(k, v)| (*k, *v)).eq(expected.iter().map(|(k, v)| (*k, *v))));
+
+        map.retain(|key, value| {
+            *value += 1;
+            key % 3 != 0
+        });
+        expected.retain(|key, value| {
+            *value += 1;
+            key % 3 != 0
+        });
+        assert_eq!(map.len(), expected.len());
+        assert!(map.iter().map(|(k, v)| (*k, *v)).eq(expected.iter().map(|(k, v)| (*k, *v))));
+        for (rank, key) in expected.keys().enumerate() {
+            assert_eq!(map.nth_key_value(rank).map(|(k, _)| k), Some(key));
+        }
+        assert!(snapshot.iter().map(|(k, v)| (*k, *v)).eq(left.iter().copied().collect::<std::collections::BTreeMap<_, _>>()));
+    }
+}
+
+#[test]
+fn update_panic_keeps_map() {
+    let mut map: AVLTreeMap<i32, i32> = (0..10).map(|key| (key, key)).collect();
+    let shared = map.clone();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| map.update(&5, |_| panic!("in update"))));
+    assert!(result.is_err());
+    assert_eq!(map.len(), 10);
+    assert!(!map.is_empty());
+    assert!(map.iter().eq(shared.iter()));
+    assert_eq!(map.update(&5, |value| *value *= 10), Some(()));
+    assert_eq!(map.get(&5), Some(&50));
+    assert_eq!(shared.get(&5), Some(&5));
+
+    // A change made before the panic is counted in the summaries.
+    let mut sums: AVLTreeMap<i32, i64, Sum> = (0..100).map(|key| (key, 1)).collect();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        sums.update(&50, |value| {
+            *value = 100;
+            panic!("after the change")
+        })
+    }));
+    assert!(result.is_err());
+    assert_eq!(sums.get(&50), Some(&100));
+    assert_eq!(sums.summary(), 199);
+    assert_eq!(sums.fold_range(40..60), 119);
+}
+
+// A map that answers every query as its entries in order would.
+fn assert_whole(map: &AVLTreeMap<i32, i32>, keys: &[i32]) {
+    assert_eq!(map.len(), keys.len());
+    assert!(map.iter().map(|(key, _)| *key).eq(keys.iter().copied()));
+    for (index, key) in keys.iter().enumerate() {
+        assert_eq!(map.nth_key_value(index).map(|(key, _)| *key), Some(*key));
+        assert!(map.contains_key(key));
+    }
+}
+
+#[test]
+fn retain_and_union_survive_panics() {
+    let mut map: AVLTreeMap<i32, i32> = (0..1000).map(|key| (key, key)).collect();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        map.retain(|key, _| if *key == 500 { panic!() } else { key % 2 == 0 })
+    }));
+    assert!(result.is_err());
+    let kept: Vec<i32> = (0..500).step_by(2).chain(500..1000).collect();
+    assert_whole(&map, &kept);
+    map.insert(1, 1);
+    map.remove(&998);
+    assert_eq!(map.len(), kept.len());
+
+    let mut map: AVLTreeMap<i32, i32> = (0..1000).step_by(2).map(|key| (key, 0)).collect();
+    let other: AVLTreeMap<i32, i32> = (0..1000).map(|key| (key, 1)).collect();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        map.union_with(other, |key, own, other| if *key == 500 { panic!() } else { own + other })
+    }));
+    assert!(result.is_err());
+    // Every key but the one being merged, with one of its values.
+    let keys: Vec<i32> = (0..1000).filter(|key| *key != 500).collect();
+    assert_whole(&map, &keys);
+    assert!(map.iter().all(|(key, value)| *value == (key % 2 == 1) as i32 || *value == 1));
+}
@@ -0,0 +1,69 @@
+use avltree::AVLTreeMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+fn accounts() -> AVLTreeMap<&'static str, i64> {
+    [("alice", 100), ("bob", 50), ("carol", 0)].into_iter().collect()
+}
+
+fn transfer(map: &mut AVLTreeMap<&'static str, i64>, from: &'static str, to: &'static str, amount: i64) -> Result<(), String> {
+    map.transaction(|tx| {
+        let balance = *tx.get(from).ok_or("no such account")?;
+        tx.insert(from, balance - amount);
+        if balance < amount {
+            return Err(format!("{from} has only {balance}"));
+        }
+        let other = tx.get(to).copied().unwrap_or(0);
+        tx.insert(to, other + amount);
+        Ok(())
+    })
+}
+
+#[test]
+fn commits_or_rolls_back() {
+    let mut map = accounts();
+    assert_eq!(transfer(&mut map, "alice", "dave", 30), Ok(()));
+    assert_eq!(map.get("alice"), Some(&70));
+    assert_eq!(map.get("dave"), Some(&30));
+
+    assert_eq!(transfer(&mut map, "bob", "carol", 80), Err("bob has only 50".to_string()));
+    assert!(map.iter().map(|(k, v)| (*k, *v)).eq([("alice", 70), ("bob", 50), ("carol", 0), ("dave", 30)]));
+
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        map.transaction(|tx| {
+            tx.remove("alice");
+            tx.insert("eve", 1);
+            assert_eq!(tx.len(), 4);
+            assert!(tx.range("b".."e").map(|(k, _)| *k).eq(["bob", "carol", "dave"]));
+            panic!("interrupted");
+            #[allow(unreachable_code)]
+            Ok::<(), ()>(())
+        })
+    }));
+    assert!(result.is_err());
+    assert_eq!(map.len(), 4);
+    assert_eq!(map.get("alice"), Some(&70));
+    assert!(!map.contains_key("eve"));
+}
+
+#[test]
+fn savepoints() {
+    let mut map: AVLTreeMap<i32, i32> = (0..10).map(|key| (key, key)).collect();
+    let removed = map.transaction(|tx| {
+        tx.remove(&0);
+        let inner: Result<(), ()> = tx.savepoint(|tx| {
+            tx.remove(&1);
+            tx.savepoint(|tx| {
+                tx.insert(100, 100);
+                Ok::<_, ()>(())
+            })?;
+            assert!(tx.contains_key(&100));
+            Err(())
+        });
+        assert!(inner.is_err());
+        assert!(tx.contains_key(&1) && !tx.contains_key(&100));
+        tx.savepoint(|tx| tx.remove(&2).ok_or(()))?;
+        Ok::<_, ()>(tx.len())
+    });
+    assert_eq!(removed, Ok(8));
+    assert!(map.iter().map(|(key, _)| *key).eq([1, 3, 4, 5, 6, 7, 8, 9]));
+}
@@ -0,0 +1,107 @@
+use avltree::AVLVec;
+use rand::{thread_rng, Rng as _};
+
+#[test]
+fn positional_edits() {
+    let mut vec: AVLVec<i32> = (0..10).collect();
+    vec.insert(0, -1);
+    vec.insert(11, 10);
+    vec.insert(5, 100);
+    assert_eq!(vec.len(), 13);
+    assert_eq!(vec[5], 100);
+    assert_eq!(vec.remove(5), 100);
+    assert_eq!(vec.first(), Some(&-1));
+    assert_eq!(vec.last(), Some(&10));
+    vec[3] *= 10;
+    assert_eq!(vec.iter().copied().collect::<Vec<_>>(), [-1, 0, 1, 20, 3, 4, 5, 6, 7, 8, 9, 10]);
+    assert_eq!(vec.range(2..5).copied().collect::<Vec<_>>(), [1, 20, 3]);
+    assert_eq!(vec.range(10..).len(), 2);
+    assert_eq!(vec.pop(), Some(10));
+    assert_eq!(vec.get(11), None);
+}
+
+#[test]
+#[should_panic(expected = "insertion index (is 3) should be <= len (is 2)")]
+fn insert_out_of_bounds() {
+    let mut vec: AVLVec<i32> = (0..2).collect();
+    vec.insert(3, 0);
+}
+
+#[test]
+fn split_off_and_append() {
+    let mut vec: AVLVec<i32> = (0..1000).collect();
+    let mut tail = vec.split_off(300);
+    assert_eq!(vec.len(), 300);
+    assert_eq!(tail.len(), 700);
+    assert_eq!(vec.last(), Some(&299));
+    assert_eq!(tail.first(), Some(&300));
+
+    let mut short: AVLVec<i32> = (-3..0).collect();
+    short.append(&mut vec);
+    assert!(vec.is_empty());
+    short.append(&mut tail);
+    assert!(short.iter().copied().eq(-3..1000));
+    assert!(short.split_off(1003).is_empty());
+    assert_eq!(short.split_off(0).len(), 1003);
+    assert!(short.is_empty());
+}
+
+#[test]
+fn random_against_vec() {
+    let mut rng = thread_rng();
+    let mut vec = AVLVec::new();
+    let mut reference = Vec::new();
+    let mut snapshots = Vec::new();
+    for step in 0..20000 {
+        match rng.gen_range(0..12) {
+            0..=4 => {
+                let index = rng.gen_range(0..=reference.len());
+                vec.insert(index, step);
+                reference.insert(index, step);
+            }
+            5..=7 if !reference.is_empty() => {
+                let index = rng.gen_range(0..reference.len());
+                assert_eq!(vec.remove(index), reference.remove(index));
+            }
+            8 if !reference.is_empty() => {
+                let index = rng.gen_range(0..reference.len());
+                vec[index] += 1;
+                reference[index] += 1;
+            }
+            9 => {
+                let at = rng.gen_range(0..=reference.len());
+                let mut tail = vec.split_off(at);
+                let mut reference_tail = reference.split_off(at);
+                assert!(tail.iter().eq(reference_tail.iter()));
+                if rng.gen_bool(0.5) {
+                    vec.append(&mut tail);
+                    reference.append(&mut reference_tail);
+                } else {
+                    tail.append(&mut vec);
+                    reference_tail.append(&mut reference);
+                    vec = tail;
+                    reference = reference_tail;
+                }
+            }
+            10 if rng.gen_bool(0.2) => {
+                let count = rng.gen_range(0..50);
+                vec.extend(step..step + count);
+                reference.extend(step..step + count);
+            }
+            10 => snapshots.push((vec.clone(), reference.clone())),
+            _ => {
+                let start = rng.gen_range(0..=reference.len());
+                let end = rng.gen_range(start..=reference.len());
+                assert!(vec.range(start..end).eq(reference[start..end].iter()));
+            }
+        }
+        assert_eq!(vec.len(), reference.len());
+    }
+    assert!(vec.iter().eq(reference.iter()));
+    for (vec, reference) in snapshots {
+        assert!(vec.iter().eq(reference.iter()));
+        for (index, value) in reference.iter().enumerate().step_by(7) {
+            assert_eq!(vec.get(index), Some(value));
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use avltree::VersionedAVLMap;
+use rand::{rngs::StdRng, Rng as _, SeedableRng as _};
+use std::collections::BTreeMap;
+
+#[test]
+fn queries_past_versions() {
+    let mut rng = StdRng::seed_from_u64(23);
+    let mut map = VersionedAVLMap::new();
+    let mut history = vec![BTreeMap::new()];
+    for _ in 0..3000 {
+        let key = rng.gen_range(0..300);
+        let mut expected = history.last().unwrap().clone();
+        if rng.gen_bool(0.3) {
+            if map.remove(&key).is_none() {
+                assert!(!expected.contains_key(&key));
+                continue;
+            }
+            expected.remove(&key);
+        } else {
+            let value = rng.gen::<u32>();
+            assert_eq!(map.insert(key, value), expected.insert(key, value));
+        }
+        history.push(expected);
+        assert_eq!(map.version() as usize, history.len() - 1);
+    }
+    for (version, expected) in history.iter().enumerate().step_by(7) {
+        let version = version as u64;
+        assert_eq!(map.len_at(version), expected.len());
+        for key in (0..300).step_by(11) {
+            assert_eq!(map.get_at(version, &key), expected.get(&key));
+        }
+        assert!(map.range_at(version, 50..150).eq(expected.range(50..150)));
+        for index in [0, expected.len() / 2, expected.len()] {
+            assert_eq!(map.nth_at(version, index), expected.iter().nth(index));
+        }
+    }
+    assert!(map.map().iter().eq(history.last().unwrap().iter()));
+}
+
+#[test]
+fn collects_old_versions() {
+    let mut map = VersionedAVLMap::new();
+    for key in 0..100 {
+        map.insert(key, key);
+    }
+    assert_eq!(map.remove(&1000), None);
+    assert_eq!(map.version(), 100);
+    map.gc_before(60);
+    assert_eq!(map.oldest_version(), 60);
+    assert!(map.at(59).is_none());
+    assert_eq!(map.len_at(60), 60);
+    assert_eq!(map.get_at(60, &59), Some(&59));
+    assert_eq!(map.get_at(60, &60), None);
+    assert!(map.at(101).is_none());
+
+    map.gc_before(1000);
+    assert_eq!(map.oldest_version(), 100);
+    map.remove(&0);
+    assert_eq!(map.len_at(100), 100);
+    assert_eq!(map.len_at(101), 99);
+    assert_eq!(map.len(), 99);
+}
+
+#[test]
+#[should_panic(expected = "version 3 is not kept")]
+fn collected_version_panics() {
+    let mut map = VersionedAVLMap::new();
+    for key in 0..10 {
+        map.insert(key, ());
+    }
+    map.gc_before(5);
+    map.len_at(3);
+}
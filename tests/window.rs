@@ -0,0 +1,63 @@
+use avltree::AVLRollingWindow;
+use rand::{thread_rng, Rng as _};
+use std::collections::VecDeque;
+
+#[test]
+fn last_n_samples() {
+    let mut window = AVLRollingWindow::last_n(3);
+    for latency in [30, 10, 20, 50] {
+        window.push(latency);
+    }
+    assert_eq!(window.len(), 3);
+    assert_eq!(window.iter().copied().collect::<Vec<_>>(), [10, 20, 50]);
+    assert_eq!(window.median(), Some(&20));
+    assert_eq!(window.min(), Some(&10));
+    assert_eq!(window.max(), Some(&50));
+    window.push(5);
+    assert_eq!(window.min(), Some(&5));
+    assert_eq!(window.percentile_rank(&20), Some(2.0 / 3.0));
+}
+
+#[test]
+fn last_span_of_time() {
+    let mut window = AVLRollingWindow::last_span(10);
+    window.push_at(0, 100);
+    window.push_at(4, 200);
+    window.push_at(9, 300);
+    assert_eq!(window.len(), 3);
+    window.push_at(10, 400);
+    assert_eq!(window.iter().copied().collect::<Vec<_>>(), [200, 300, 400]);
+    window.push(50);
+    assert_eq!(window.min(), Some(&50));
+    window.advance_to(19);
+    assert_eq!(window.iter().copied().collect::<Vec<_>>(), [50, 400]);
+    window.advance_to(100);
+    assert!(window.is_empty());
+    assert_eq!(window.median(), None);
+}
+
+#[test]
+fn rolling_percentiles_match_sorting() {
+    let mut rng = thread_rng();
+    let mut window = AVLRollingWindow::last_n(200);
+    let mut reference = VecDeque::new();
+    for _ in 0..5000 {
+        let latency = rng.gen_range(0..1000);
+        window.push(latency);
+        reference.push_back(latency);
+        if reference.len() > 200 {
+            reference.pop_front();
+        }
+        let mut sorted: Vec<_> = reference.iter().copied().collect();
+        sorted.sort_unstable();
+        let p99 = sorted[(0.99 * (sorted.len() - 1) as f64) as usize];
+        assert_eq!(window.quantile(0.99), Some(&p99));
+        assert_eq!(window.median(), Some(&sorted[(sorted.len() - 1) / 2]));
+    }
+}
+
+#[test]
+#[should_panic(expected = "a window should span at least one time unit")]
+fn empty_span() {
+    AVLRollingWindow::<i32>::last_span(0);
+}